@@ -0,0 +1,62 @@
+//! Compares the bulk binary vertex reader against the generic ply-rs parser.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ennona::import::ply::{import_ply, PlyVertex};
+use ply_rs::parser::Parser;
+use std::io::Cursor;
+
+/// Creates a `binary_little_endian` PLY file with positions, colors and an intensity value.
+fn binary_cloud(num_points: usize) -> Vec<u8> {
+    let mut data = format!(
+        "ply\n\
+        format binary_little_endian 1.0\n\
+        element vertex {}\n\
+        property float x\n\
+        property float y\n\
+        property float z\n\
+        property uchar red\n\
+        property uchar green\n\
+        property uchar blue\n\
+        property float intensity\n\
+        end_header\n",
+        num_points
+    )
+    .into_bytes();
+    for i in 0..num_points {
+        let t = i as f32;
+        for v in [t.sin(), t.cos(), t * 0.001] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend_from_slice(&[i as u8, (i >> 8) as u8, (i >> 16) as u8]);
+        data.extend_from_slice(&(t * 0.5).to_le_bytes());
+    }
+    data
+}
+
+fn ply_vertices(c: &mut Criterion) {
+    let mut group = c.benchmark_group("binary_ply_vertices");
+    group.sample_size(10);
+    for num_points in [100_000, 1_000_000] {
+        let data = binary_cloud(num_points);
+        group.throughput(Throughput::Elements(num_points as u64));
+
+        group.bench_with_input(BenchmarkId::new("bulk", num_points), &data, |b, data| {
+            b.iter(|| import_ply(Cursor::new(black_box(data))).unwrap())
+        });
+
+        group.bench_with_input(BenchmarkId::new("ply-rs", num_points), &data, |b, data| {
+            b.iter(|| {
+                let mut reader = Cursor::new(black_box(data));
+                let parser = Parser::<PlyVertex>::new();
+                let header = parser.read_header(&mut reader).unwrap();
+                parser
+                    .read_payload_for_element(&mut reader, &header.elements["vertex"], &header)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, ply_vertices);
+criterion_main!(benches);
@@ -114,12 +114,10 @@ impl CameraController {
             VirtualKeyCode::T => {
                 self.is_clock_pressed = is_pressed;
             }
-            VirtualKeyCode::Escape => {
-                if is_pressed {
-                    self.mouse_captured = !self.mouse_captured;
-                    let _ = window.set_cursor_grab(self.mouse_captured);
-                    window.set_cursor_visible(!self.mouse_captured);
-                }
+            VirtualKeyCode::Escape if is_pressed => {
+                self.mouse_captured = !self.mouse_captured;
+                let _ = window.set_cursor_grab(self.mouse_captured);
+                window.set_cursor_visible(!self.mouse_captured);
             }
             _ => {}
         }
@@ -0,0 +1,283 @@
+pub mod las;
+pub mod pcd;
+pub mod ply;
+pub mod xyz;
+
+use crate::{
+    import::{PlyData, ScalarField},
+    points::Vertex,
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use thiserror::Error;
+
+/// How the values of a file are written, for formats that have a text and a binary variant, PLY
+/// and PCD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    Ascii,
+    /// Binary with little endian numbers.
+    #[default]
+    Binary,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(Encoding::Ascii),
+            "binary" => Ok(Encoding::Binary),
+            _ => Err(format!(
+                "unknown encoding '{}', expected ascii or binary",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    pub encoding: Encoding,
+}
+
+/// An error that occurred while exporting to a file.
+#[derive(Debug, Error)]
+#[error("failed to export '{}': {kind}", path.display())]
+pub struct ExportError {
+    /// The file that was being written.
+    pub path: PathBuf,
+    pub kind: ExportErrorKind,
+}
+
+#[derive(Debug, Error)]
+pub enum ExportErrorKind {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("unknown file extension '{0}'")]
+    UnknownExtension(String),
+    #[error("{0}")]
+    Las(Box<::las::Error>),
+}
+
+impl From<::las::Error> for ExportErrorKind {
+    fn from(error: ::las::Error) -> Self {
+        // boxed, since it is much larger than the other variants
+        ExportErrorKind::Las(Box::new(error))
+    }
+}
+
+/// Writes the data to a file in the format of its extension.
+pub fn export(path: &Path, ply_data: &PlyData, options: &ExportOptions) -> Result<(), ExportError> {
+    export_file(path, ply_data, options).map_err(|kind| ExportError {
+        path: path.to_owned(),
+        kind,
+    })
+}
+
+fn export_file(
+    path: &Path,
+    ply_data: &PlyData,
+    options: &ExportOptions,
+) -> Result<(), ExportErrorKind> {
+    let extension = path
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .unwrap_or("no_extension");
+    // checked before the file is created, so nothing is left behind
+    if !matches!(
+        extension,
+        "ply" | "pcd" | "las" | "laz" | "xyz" | "csv" | "txt"
+    ) {
+        return Err(ExportErrorKind::UnknownExtension(extension.to_owned()));
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    match extension {
+        "ply" => ply::export_ply(&mut writer, ply_data, options.encoding)?,
+        "pcd" => pcd::export_pcd(&mut writer, ply_data, options.encoding)?,
+        // the las writer has to own the file, and flushes it itself
+        "las" | "laz" => return las::export_las(writer, ply_data, extension == "laz"),
+        _ => {
+            let delimiter = if extension == "csv" { ',' } else { ' ' };
+            xyz::export_xyz(&mut writer, ply_data, delimiter)?
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Returns the points followed by the face vertices, which is the order all writers use.
+fn vertices(ply_data: &PlyData) -> impl Iterator<Item = &Vertex> {
+    ply_data
+        .point_vertices
+        .iter()
+        .chain(&ply_data.face_vertices)
+}
+
+/// Returns the value of a scalar field for vertex `i` in the order of [`vertices`].
+fn field_value(ply_data: &PlyData, field: &ScalarField, i: usize) -> f32 {
+    let point_count = ply_data.point_vertices.len();
+    if i < point_count {
+        field.point_values.get(i)
+    } else {
+        field.face_values.get(i - point_count)
+    }
+    .copied()
+    .unwrap_or(f32::NAN)
+}
+
+/// Returns the original coordinates of a vertex.
+fn position(vertex: &Vertex, origin: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|i| vertex.position[i] as f64 + origin[i])
+}
+
+fn color_bytes(vertex: &Vertex) -> [u8; 3] {
+    vertex
+        .color
+        .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// Makes a scalar field name usable as a property name, which can't contain whitespace.
+fn property_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{self, progress::Progress, Import, ImportOptions};
+
+    /// Georeferenced points with colors and the scalar fields that LAS files have.
+    fn cloud() -> PlyData {
+        let positions = [[0.25, 0.5, 1.0], [-1.0, 2.0, 0.0], [3.0, -0.5, -2.0]];
+        let colors = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let field = |name: &str, point_values: Vec<f32>| ScalarField {
+            name: name.into(),
+            point_values,
+            face_values: Vec::new(),
+        };
+        PlyData {
+            point_vertices: positions
+                .iter()
+                .zip(&colors)
+                .map(|(&position, &color)| Vertex {
+                    position,
+                    _padding0: [0; 4],
+                    color,
+                    _padding1: [0; 4],
+                })
+                .collect(),
+            scalar_fields: vec![
+                field("intensity", vec![10.0, 20.0, 30.0]),
+                field("classification", vec![2.0, 6.0, 2.0]),
+            ],
+            origin: [1000.0, 2000.0, 10.0],
+            ..Default::default()
+        }
+    }
+
+    /// Writes the cloud to a file named `name` and imports it again.
+    fn roundtrip(name: &str, encoding: Encoding) -> PlyData {
+        let dir = std::env::temp_dir().join(format!("ennona-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        export(&path, &cloud(), &ExportOptions { encoding }).unwrap();
+        let imported = import::import(&path, &ImportOptions::default(), &Progress::default());
+        std::fs::remove_file(&path).unwrap();
+        match imported.unwrap() {
+            Import::Ply(ply_data) => ply_data,
+            _ => panic!("'{}' was not imported as geometry", name),
+        }
+    }
+
+    fn field<'a>(ply_data: &'a PlyData, name: &str) -> &'a [f32] {
+        let field = ply_data
+            .scalar_fields
+            .iter()
+            .find(|field| field.name == name);
+        &field.unwrap().point_values
+    }
+
+    fn assert_same_cloud(exported: &PlyData, name: &str) {
+        let original = cloud();
+        assert_eq!(
+            exported.point_vertices.len(),
+            original.point_vertices.len(),
+            "{}",
+            name
+        );
+        for (a, b) in exported.point_vertices.iter().zip(&original.point_vertices) {
+            let a_position = position(a, exported.origin);
+            let b_position = position(b, original.origin);
+            for (a, b) in a_position.iter().zip(&b_position) {
+                assert!(
+                    (a - b).abs() < 1e-3,
+                    "{}: {:?} {:?}",
+                    name,
+                    a_position,
+                    b_position
+                );
+            }
+            assert_eq!(a.color, b.color, "{}", name);
+        }
+        for field_name in ["intensity", "classification"] {
+            let values = field(exported, field_name);
+            assert_eq!(values, field(&original, field_name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn pcd_roundtrip() {
+        for encoding in [Encoding::Ascii, Encoding::Binary] {
+            assert_same_cloud(&roundtrip("cloud.pcd", encoding), "cloud.pcd");
+        }
+    }
+
+    #[test]
+    fn las_roundtrip() {
+        for name in ["cloud.las", "cloud.laz"] {
+            assert_same_cloud(&roundtrip(name, Encoding::default()), name);
+        }
+    }
+
+    #[test]
+    fn las_ignores_values_that_are_no_class() {
+        use ::las::{point::Classification, Read};
+
+        let mut ply_data = cloud();
+        ply_data.scalar_fields[1].point_values = vec![12.7, -3.0, 12.0];
+        let dir = std::env::temp_dir().join(format!("ennona-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("classes.las");
+        export(&path, &ply_data, &ExportOptions::default()).unwrap();
+        let points = ::las::Reader::from_path(&path)
+            .unwrap()
+            .points()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let overlaps = points.iter().map(|point| point.is_overlap);
+        assert!(overlaps.eq([false, false, true]));
+        // values that are no class leave the points unclassified
+        assert_eq!(
+            points[0].classification,
+            Classification::CreatedNeverClassified
+        );
+        assert_eq!(
+            points[1].classification,
+            Classification::CreatedNeverClassified
+        );
+    }
+
+    #[test]
+    fn text_roundtrip() {
+        for name in ["cloud.xyz", "cloud.csv", "cloud.txt"] {
+            assert_same_cloud(&roundtrip(name, Encoding::default()), name);
+        }
+    }
+}
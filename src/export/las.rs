@@ -0,0 +1,88 @@
+//! Writer for ASPRS LAS files, optionally LAZ compressed.
+
+use super::{field_value, position, vertices, ExportErrorKind};
+use crate::import::PlyData;
+use las::{
+    point::{Classification, Format},
+    Builder, Color, Point, Transform, Vector, Write as _, Writer,
+};
+use std::{
+    fmt::Debug,
+    io::{Seek, Write},
+};
+
+/// Points are stored as integers in steps of this many meters.
+const SCALE: f64 = 0.001;
+
+/// Writes all vertices with their colors, along with the `intensity` and `classification` scalar
+/// fields if the data has them.
+pub fn export_las<W: Write + Seek + Send + Debug + 'static>(
+    writer: W,
+    ply_data: &PlyData,
+    compressed: bool,
+) -> Result<(), ExportErrorKind> {
+    let field = |name: &str| {
+        ply_data
+            .scalar_fields
+            .iter()
+            .find(|field| field.name == name)
+    };
+    let intensity = field("intensity");
+    let classification = field("classification");
+
+    let mut builder = Builder::from((1, 2));
+    builder.generating_software = "ennona".into();
+    // point format 2 has colors, but no GPS time
+    builder.point_format = Format::new(2)?;
+    builder.point_format.is_compressed = compressed;
+    // the origin keeps the integer coordinates small
+    let [x, y, z] = ply_data.origin;
+    builder.transforms = Vector {
+        x: Transform {
+            scale: SCALE,
+            offset: x,
+        },
+        y: Transform {
+            scale: SCALE,
+            offset: y,
+        },
+        z: Transform {
+            scale: SCALE,
+            offset: z,
+        },
+    };
+    let mut writer = Writer::new(writer, builder.into_header()?)?;
+
+    for (i, vertex) in vertices(ply_data).enumerate() {
+        let [x, y, z] = position(vertex, ply_data.origin);
+        let [red, green, blue] = vertex
+            .color
+            .map(|c| (c.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16);
+        let mut point = Point {
+            x,
+            y,
+            z,
+            color: Some(Color { red, green, blue }),
+            ..Default::default()
+        };
+        if let Some(intensity) = intensity {
+            let value = field_value(ply_data, intensity, i);
+            point.intensity = value.round().clamp(0.0, u16::MAX as f32) as u16;
+        }
+        if let Some(classification) = classification {
+            let value = field_value(ply_data, classification, i);
+            // point format 2 only has five bits for the class, other values aren't classes
+            if value.fract() == 0.0 && (0.0..=31.0).contains(&value) {
+                match value as u8 {
+                    // overlap is a flag of its own in the las crate
+                    12 => point.is_overlap = true,
+                    class => point.classification = Classification::new(class)?,
+                }
+            }
+        }
+        writer.write(point)?;
+    }
+    // closing the writer fills in the header, but leaves flushing to the caller
+    writer.into_inner()?.flush()?;
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! Writer for PCD files of the Point Cloud Library, with the colors packed into an `rgb` field.
+
+use super::{color_bytes, field_value, position, property_name, vertices, Encoding};
+use crate::import::PlyData;
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::io::{self, Write};
+
+/// Writes all vertices as an unorganized cloud, followed by the scalar fields as float fields.
+///
+/// Positions are written as doubles when the data has an origin, like in PLY files.
+pub fn export_pcd(writer: impl Write, ply_data: &PlyData, encoding: Encoding) -> io::Result<()> {
+    let mut writer = writer;
+    let count = ply_data.point_vertices.len() + ply_data.face_vertices.len();
+    let double = ply_data.origin != [0.0; 3];
+    let position_size = if double { 8 } else { 4 };
+    let field_count = ply_data.scalar_fields.len();
+
+    let mut names = ["x", "y", "z", "rgb"].map(String::from).to_vec();
+    names.extend(
+        ply_data
+            .scalar_fields
+            .iter()
+            .map(|field| property_name(&field.name)),
+    );
+    let sizes = [position_size; 3]
+        .iter()
+        .chain(&[4])
+        .chain(&vec![4; field_count])
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let types = ["F", "F", "F", "U"]
+        .iter()
+        .copied()
+        .chain(vec!["F"; field_count])
+        .collect::<Vec<_>>();
+
+    writeln!(writer, "# .PCD v0.7 - Point Cloud Data file format")?;
+    writeln!(writer, "VERSION 0.7")?;
+    writeln!(writer, "FIELDS {}", names.join(" "))?;
+    writeln!(writer, "SIZE {}", sizes.join(" "))?;
+    writeln!(writer, "TYPE {}", types.join(" "))?;
+    writeln!(writer, "COUNT {}", vec!["1"; names.len()].join(" "))?;
+    writeln!(writer, "WIDTH {}", count)?;
+    writeln!(writer, "HEIGHT 1")?;
+    writeln!(writer, "VIEWPOINT 0 0 0 1 0 0 0")?;
+    writeln!(writer, "POINTS {}", count)?;
+    match encoding {
+        Encoding::Ascii => writeln!(writer, "DATA ascii")?,
+        Encoding::Binary => writeln!(writer, "DATA binary")?,
+    }
+
+    for (i, vertex) in vertices(ply_data).enumerate() {
+        let [r, g, b] = color_bytes(vertex);
+        let rgb = u32::from_be_bytes([0, r, g, b]);
+        let values = ply_data
+            .scalar_fields
+            .iter()
+            .map(|field| field_value(ply_data, field, i));
+        match encoding {
+            Encoding::Ascii => {
+                if double {
+                    let [x, y, z] = position(vertex, ply_data.origin);
+                    write!(writer, "{} {} {}", x, y, z)?;
+                } else {
+                    let [x, y, z] = vertex.position;
+                    write!(writer, "{} {} {}", x, y, z)?;
+                }
+                write!(writer, " {}", rgb)?;
+                for value in values {
+                    write!(writer, " {}", value)?;
+                }
+                writeln!(writer)?;
+            }
+            Encoding::Binary => {
+                if double {
+                    for value in position(vertex, ply_data.origin) {
+                        writer.write_f64::<LittleEndian>(value)?;
+                    }
+                } else {
+                    for value in vertex.position {
+                        writer.write_f32::<LittleEndian>(value)?;
+                    }
+                }
+                writer.write_u32::<LittleEndian>(rgb)?;
+                for value in values {
+                    writer.write_f32::<LittleEndian>(value)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+//! Writer for PLY files with the points, faces, colors and scalar fields of the data.
+
+use super::{color_bytes, field_value, position, property_name, vertices, Encoding};
+use crate::import::PlyData;
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::io::{self, Write};
+
+/// Writes the points and face vertices as one vertex element, followed by the triangles.
+///
+/// Positions are written as doubles when the data has an origin, since adding it back loses
+/// precision in a float.
+pub fn export_ply(writer: impl Write, ply_data: &PlyData, encoding: Encoding) -> io::Result<()> {
+    let mut writer = writer;
+    let vertex_count = ply_data.point_vertices.len() + ply_data.face_vertices.len();
+    let point_count = ply_data.point_vertices.len();
+    let double = ply_data.origin != [0.0; 3];
+
+    writeln!(writer, "ply")?;
+    match encoding {
+        Encoding::Ascii => writeln!(writer, "format ascii 1.0")?,
+        Encoding::Binary => writeln!(writer, "format binary_little_endian 1.0")?,
+    }
+    writeln!(writer, "comment exported by ennona")?;
+    writeln!(writer, "element vertex {}", vertex_count)?;
+    let position_type = if double { "double" } else { "float" };
+    for axis in ["x", "y", "z"] {
+        writeln!(writer, "property {} {}", position_type, axis)?;
+    }
+    for color in ["red", "green", "blue"] {
+        writeln!(writer, "property uchar {}", color)?;
+    }
+    for field in &ply_data.scalar_fields {
+        writeln!(writer, "property float {}", property_name(&field.name))?;
+    }
+    if !ply_data.face_indices.is_empty() {
+        writeln!(writer, "element face {}", ply_data.face_indices.len() / 3)?;
+        writeln!(writer, "property list uchar uint vertex_indices")?;
+    }
+    writeln!(writer, "end_header")?;
+
+    for (i, vertex) in vertices(ply_data).enumerate() {
+        let position = position(vertex, ply_data.origin);
+        let color = color_bytes(vertex);
+        let values = ply_data
+            .scalar_fields
+            .iter()
+            .map(|field| field_value(ply_data, field, i));
+        match encoding {
+            Encoding::Ascii => {
+                if double {
+                    write!(writer, "{} {} {}", position[0], position[1], position[2])?;
+                } else {
+                    let [x, y, z] = vertex.position;
+                    write!(writer, "{} {} {}", x, y, z)?;
+                }
+                write!(writer, " {} {} {}", color[0], color[1], color[2])?;
+                for value in values {
+                    write!(writer, " {}", value)?;
+                }
+                writeln!(writer)?;
+            }
+            Encoding::Binary => {
+                if double {
+                    for value in position {
+                        writer.write_f64::<LittleEndian>(value)?;
+                    }
+                } else {
+                    for value in vertex.position {
+                        writer.write_f32::<LittleEndian>(value)?;
+                    }
+                }
+                writer.write_all(&color)?;
+                for value in values {
+                    writer.write_f32::<LittleEndian>(value)?;
+                }
+            }
+        }
+    }
+
+    // face vertices come after the points
+    let offset = point_count as u32;
+    for triangle in ply_data.face_indices.chunks_exact(3) {
+        match encoding {
+            Encoding::Ascii => writeln!(
+                writer,
+                "3 {} {} {}",
+                triangle[0] + offset,
+                triangle[1] + offset,
+                triangle[2] + offset
+            )?,
+            Encoding::Binary => {
+                writer.write_u8(3)?;
+                for &index in triangle {
+                    writer.write_u32::<LittleEndian>(index + offset)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! Writer for points as delimited text, with a header line naming the columns.
+
+use super::{color_bytes, field_value, position, property_name, vertices};
+use crate::import::PlyData;
+use std::io::{self, Write};
+
+/// Writes one line per vertex with its position, its color from 0 to 255 and its scalar fields.
+pub fn export_xyz(writer: impl Write, ply_data: &PlyData, delimiter: char) -> io::Result<()> {
+    let mut writer = writer;
+    let mut names = ["x", "y", "z", "r", "g", "b"].map(String::from).to_vec();
+    names.extend(
+        ply_data
+            .scalar_fields
+            .iter()
+            .map(|field| property_name(&field.name)),
+    );
+    writeln!(writer, "{}", names.join(&delimiter.to_string()))?;
+
+    for (i, vertex) in vertices(ply_data).enumerate() {
+        let [x, y, z] = position(vertex, ply_data.origin);
+        let [r, g, b] = color_bytes(vertex);
+        write!(
+            writer,
+            "{x}{d}{y}{d}{z}{d}{r}{d}{g}{d}{b}",
+            x = x,
+            y = y,
+            z = z,
+            r = r,
+            g = g,
+            b = b,
+            d = delimiter
+        )?;
+        for field in &ply_data.scalar_fields {
+            write!(writer, "{}{}", delimiter, field_value(ply_data, field, i))?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
@@ -1,160 +1,579 @@
-use crate::points::Vertex;
-use eyre::{eyre, Result};
-use itertools::Itertools;
-use nalgebra::{distance, Point3, Vector3};
-use ply_rs::{parser::Parser, ply};
-use std::{collections::HashMap, path::Path};
+pub mod bundler;
+pub mod colmap;
+pub mod compression;
+pub mod depth;
+pub mod e57;
+pub mod format;
+pub mod gltf;
+pub mod kitti;
+pub mod las;
+pub mod nvm;
+pub mod obj;
+pub mod pcd;
+pub mod ply;
+pub mod progress;
+pub mod stl;
+pub mod tum;
+pub mod xyz;
 
-#[derive(Debug)]
-pub struct Face {
-    pub vertex_index: Vec<i32>,
-}
+use crate::points::{colormap, Vertex};
+use compression::Compression;
+use format::Format;
+use nalgebra::{distance, IsometryMatrix3, Point3, Translation3, Vector3};
+use progress::{Progress, ProgressReader};
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::{self, BufRead, BufReader, Cursor, Read, Seek},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
 
 pub enum Import {
     Ply(PlyData),
     Image(image::DynamicImage),
+    Sequence(Sequence),
+    /// Color and depth images with camera poses that are fused into one cloud.
+    Rgbd(tum::RgbdDataset),
+}
+
+/// Scans that are shown one after another, like the frames of a lidar recording.
+#[derive(Debug, Clone)]
+pub struct Sequence {
+    pub frames: Vec<PathBuf>,
+}
+
+impl Sequence {
+    /// Imports a single frame of the sequence, reporting how much of it was read.
+    pub fn load(&self, index: usize, progress: &Progress) -> Result<PlyData, ImportError> {
+        let path = &self.frames[index];
+        let import_frame = || {
+            let file = File::open(path)?;
+            let total = file.metadata()?.len();
+            kitti::import_kitti_bin(BufReader::new(ProgressReader::new(file, total, progress)))
+        };
+        import_frame().map_err(|kind| ImportError {
+            path: path.clone(),
+            kind: if progress.is_cancelled() {
+                ImportErrorKind::Cancelled
+            } else {
+                kind
+            },
+        })
+    }
 }
 
+#[derive(Default)]
 pub struct PlyData {
     pub point_vertices: Vec<Vertex>,
     pub face_vertices: Vec<Vertex>,
     pub face_indices: Vec<u32>,
+    /// Per-vertex properties that are not rendered directly, such as normals or intensity.
+    pub scalar_fields: Vec<ScalarField>,
+    /// Camera views registered in a reconstruction.
+    pub views: Vec<CameraView>,
+    /// Offset that has to be added to all positions to get the original coordinates.
+    ///
+    /// Georeferenced data is moved close to zero, since its coordinates are too large to be
+    /// stored precisely in an `f32`.
+    pub origin: [f64; 3],
+    /// Separate point clouds that make up `point_vertices`, such as the scans of an E57 file.
+    pub clouds: Vec<Cloud>,
 }
 
-pub fn import(path: &Path) -> Result<Import> {
-    let f = std::fs::File::open(path).unwrap();
-    let mut buf_read = std::io::BufReader::new(f);
-    let extension = path
-        .extension()
-        .and_then(std::ffi::OsStr::to_str)
-        .unwrap_or("no_extension");
+impl PlyData {
+    /// Returns the geometry with the vertex colors replaced by the values of a scalar field.
+    pub fn colored_by(&self, field: &ScalarField) -> PlyData {
+        let (min, max) = field.range().unwrap_or((0.0, 0.0));
+        let recolor = |vertices: &[Vertex], values: &[f32]| {
+            vertices
+                .iter()
+                .zip(values)
+                .map(|(vertex, &value)| Vertex {
+                    color: colormap((value - min) / (max - min).max(f32::EPSILON)),
+                    ..*vertex
+                })
+                .collect()
+        };
 
-    match extension {
-        "ply" => {
-            let vertex_parser = Parser::<Vertex>::new();
-            let face_parser = Parser::<Face>::new();
-            let header = vertex_parser.read_header(&mut buf_read)?;
+        PlyData {
+            point_vertices: recolor(&self.point_vertices, &field.point_values),
+            face_vertices: recolor(&self.face_vertices, &field.face_values),
+            face_indices: self.face_indices.clone(),
+            origin: self.origin,
+            clouds: self.clouds.clone(),
+            ..Default::default()
+        }
+    }
 
-            let mut all_vertices = Vec::new();
-            let mut faces = Vec::new();
+    /// Returns the data with only the points of the clouds that are marked as visible.
+    pub fn with_visible_clouds(&self, visible: &[bool]) -> PlyData {
+        let ranges = self
+            .clouds
+            .iter()
+            .zip(visible)
+            .filter(|(_, &visible)| visible)
+            .map(|(cloud, _)| cloud.points.clone())
+            .collect::<Vec<_>>();
+        let select = |values: &[f32]| {
+            ranges
+                .iter()
+                .flat_map(|range| values[range.clone()].iter().copied())
+                .collect()
+        };
 
-            for (_, element) in &header.elements {
-                if element.name == "vertex" {
-                    all_vertices =
-                        vertex_parser.read_payload_for_element(&mut buf_read, element, &header)?;
-                }
-                if element.name == "face" {
-                    faces =
-                        face_parser.read_payload_for_element(&mut buf_read, element, &header)?;
+        let mut start = 0;
+        let clouds = self
+            .clouds
+            .iter()
+            .zip(visible)
+            .filter(|(_, &visible)| visible)
+            .map(|(cloud, _)| {
+                let points = start..start + cloud.points.len();
+                start = points.end;
+                Cloud {
+                    name: cloud.name.clone(),
+                    points,
                 }
+            })
+            .collect();
+        PlyData {
+            point_vertices: ranges
+                .iter()
+                .flat_map(|range| self.point_vertices[range.clone()].iter().copied())
+                .collect(),
+            face_vertices: self.face_vertices.clone(),
+            face_indices: self.face_indices.clone(),
+            scalar_fields: self
+                .scalar_fields
+                .iter()
+                .map(|field| ScalarField {
+                    name: field.name.clone(),
+                    point_values: select(&field.point_values),
+                    face_values: field.face_values.clone(),
+                })
+                .collect(),
+            views: self.views.clone(),
+            origin: self.origin,
+            clouds,
+        }
+    }
+
+    /// Returns the origin of a scene of several layers, which is the origin of the first layer
+    /// that has one.
+    ///
+    /// Layers without an origin are usually close to zero and small, so they lose less precision
+    /// when they are moved than georeferenced ones.
+    pub fn scene_origin<'a>(layers: impl IntoIterator<Item = &'a PlyData>) -> [f64; 3] {
+        layers
+            .into_iter()
+            .map(|layer| layer.origin)
+            .find(|&origin| origin != [0.0; 3])
+            .unwrap_or_default()
+    }
+
+    /// Combines several layers into one, in the coordinates of the scene they make up, see
+    /// [`PlyData::scene_origin`].
+    ///
+    /// Scalar fields that only some of the layers have are NaN for the vertices of the others.
+    pub fn merged(layers: &[&PlyData]) -> PlyData {
+        PlyData::merged_at(layers, PlyData::scene_origin(layers.iter().copied()))
+    }
+
+    /// Combines several layers into one, with positions relative to `origin`.
+    pub fn merged_at(layers: &[&PlyData], origin: [f64; 3]) -> PlyData {
+        let mut names: Vec<&str> = Vec::new();
+        for field in layers.iter().flat_map(|layer| &layer.scalar_fields) {
+            if !names.contains(&field.name.as_str()) {
+                names.push(&field.name);
             }
+        }
+        let mut merged = PlyData {
+            scalar_fields: names
+                .iter()
+                .map(|&name| ScalarField {
+                    name: name.to_owned(),
+                    point_values: Vec::new(),
+                    face_values: Vec::new(),
+                })
+                .collect(),
+            origin,
+            ..Default::default()
+        };
 
-            // maps from ply vertex indices to face vertex indices
-            let mut all_vertices_to_face_vertices = HashMap::new();
-            let mut face_vertices = Vec::new();
-            let mut face_indices = Vec::new();
-            // looks up face vertex index or creates face vertex for ply vertex
-            let mut get_or_insert_vertex = |index| {
-                *all_vertices_to_face_vertices
-                    .entry(index)
-                    .or_insert_with(|| {
-                        let pos = face_vertices.len() as u32;
-                        face_vertices.push(all_vertices[index]);
-                        pos
-                    })
+        for layer in layers {
+            let offset = Vector3::from(layer.origin) - Vector3::from(origin);
+            let shift = |vertex: &Vertex| Vertex {
+                position: (Vector3::from(vertex.position) + offset.cast::<f32>()).into(),
+                ..*vertex
             };
-            // tesselate faces
-            for face in faces {
-                // turns ply vertices into face vertices
-                let mut face_iter = face
-                    .vertex_index
-                    .iter()
-                    .map(|&i| get_or_insert_vertex(i as usize));
-                // first vertex is center of triangle fan and first vertex
-                // of all triangles
-                if let Some(first) = face_iter.next() {
-                    for (second, third) in face_iter.tuple_windows() {
-                        // each set of 2 indicies is a new triangle in the triangle fan.
-                        face_indices.extend_from_slice(&[first, second, third]);
-                    }
+            let point_start = merged.point_vertices.len();
+            let face_start = merged.face_vertices.len() as u32;
+            merged
+                .point_vertices
+                .extend(layer.point_vertices.iter().map(shift));
+            merged
+                .face_vertices
+                .extend(layer.face_vertices.iter().map(shift));
+            merged
+                .face_indices
+                .extend(layer.face_indices.iter().map(|&i| i + face_start));
+            for field in &mut merged.scalar_fields {
+                if let Some(values) = layer.scalar_fields.iter().find(|f| f.name == field.name) {
+                    field.point_values.extend_from_slice(&values.point_values);
+                    field.face_values.extend_from_slice(&values.face_values);
                 }
+                field
+                    .point_values
+                    .resize(merged.point_vertices.len(), f32::NAN);
+                field
+                    .face_values
+                    .resize(merged.face_vertices.len(), f32::NAN);
             }
+            merged.clouds.extend(layer.clouds.iter().map(|cloud| Cloud {
+                name: cloud.name.clone(),
+                points: cloud.points.start + point_start..cloud.points.end + point_start,
+            }));
+            merged
+                .views
+                .extend(layer.views.iter().map(|view| view.moved(offset)));
+        }
+        merged
+    }
+}
 
-            // set of vertices that do not corrispond to faces.
-            let point_vertices = all_vertices
-                .iter()
-                .enumerate()
-                .filter(|(i, _)| !all_vertices_to_face_vertices.contains_key(i))
-                .map(|(_, &v)| v)
-                .collect_vec();
-
-            let ply_data = PlyData {
-                point_vertices,
-                face_vertices,
-                face_indices,
-            };
+/// A point cloud that was loaded along with others from the same file.
+#[derive(Debug, Clone)]
+pub struct Cloud {
+    pub name: String,
+    /// Indices of the points of the cloud in `PlyData::point_vertices`.
+    pub points: Range<usize>,
+}
 
-            Ok(Import::Ply(ply_data))
-        }
-        "png" | "jpg" | "jpeg" => {
-            // let img = image::load_from_memory(buf_read)?;
-            let img = image::io::Reader::new(buf_read)
-                .with_guessed_format()?
-                .decode()?;
-            Ok(Import::Image(img))
+/// A named per-vertex property, stored alongside the vertices it belongs to.
+#[derive(Debug, Clone)]
+pub struct ScalarField {
+    pub name: String,
+    /// One value for each vertex in `PlyData::point_vertices`.
+    pub point_values: Vec<f32>,
+    /// One value for each vertex in `PlyData::face_vertices`.
+    pub face_values: Vec<f32>,
+}
+
+impl ScalarField {
+    /// Returns the smallest and largest finite value of the field, if it has any.
+    pub fn range(&self) -> Option<(f32, f32)> {
+        self.point_values
+            .iter()
+            .chain(&self.face_values)
+            .filter(|v| v.is_finite())
+            .fold(None, |range, &v| match range {
+                Some((min, max)) => Some((v.min(min), v.max(max))),
+                None => Some((v, v)),
+            })
+    }
+}
+
+/// A camera that captured the data, such as a registered image of a reconstruction.
+#[derive(Debug, Clone)]
+pub struct CameraView {
+    pub name: String,
+    /// Transforms world coordinates into camera coordinates, where the camera looks along
+    /// positive z with y pointing down.
+    pub world_to_camera: IsometryMatrix3<f32>,
+    pub intrinsics: Intrinsics,
+}
+
+impl CameraView {
+    /// Returns the view of the camera once the points it sees are moved by `offset`.
+    pub fn moved(&self, offset: Vector3<f64>) -> CameraView {
+        let to_original = Translation3::from(-offset.cast::<f32>());
+        CameraView {
+            world_to_camera: self.world_to_camera * to_original,
+            ..self.clone()
         }
-        _ => Err(eyre!(
-            "Unknown file extension '{}' in file: '{:?}'",
-            extension,
-            path
-        )),
     }
 }
 
-impl ply::PropertyAccess for Vertex {
-    fn new() -> Self {
-        Vertex {
-            position: [0.0, 0.0, 0.0],
-            _padding0: [0; 4],
-            color: [0.0, 0.0, 0.0],
-            _padding1: [0; 4],
+/// Pinhole intrinsics of a camera, in pixels.
+#[derive(Debug, Clone)]
+pub struct Intrinsics {
+    /// Name of the camera model, such as `PINHOLE` or `OPENCV`.
+    pub model: String,
+    pub width: u32,
+    pub height: u32,
+    pub fx: f32,
+    pub fy: f32,
+    pub cx: f32,
+    pub cy: f32,
+    /// Lens distortion parameters, their meaning depends on `model`.
+    pub distortion: Vec<f32>,
+}
+
+/// The path that stands for the standard input, as is usual for command line tools.
+pub const STDIN_PATH: &str = "-";
+
+/// Bytes at the start of a stream that its format is detected from.
+const HEADER_SIZE: usize = 8192;
+
+/// Upper bound of the rows that are reserved up front for a count read from a file, since the
+/// file may be truncated or corrupt.
+const MAX_RESERVED_ROWS: usize = 1 << 20;
+
+/// Settings for formats that can't be imported without knowing more about the data.
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Column mapping for delimited text files.
+    pub text: xyz::TextOptions,
+    /// If set, images are back-projected as depth maps instead of being shown as images.
+    pub depth: Option<depth::DepthOptions>,
+}
+
+/// An error that occurred while importing a file.
+#[derive(Debug, Error)]
+#[error("failed to import '{}': {kind}", path.display())]
+pub struct ImportError {
+    /// The file that was being imported.
+    pub path: PathBuf,
+    pub kind: ImportErrorKind,
+}
+
+/// The reason an import failed.
+#[derive(Debug, Error)]
+pub enum ImportErrorKind {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("invalid header: {0}")]
+    Header(String),
+    #[error("property '{property}' of element '{element}' must be {expected}")]
+    TypeMismatch {
+        element: String,
+        property: String,
+        expected: &'static str,
+    },
+    /// Rows are counted from zero, like the rows of faces below. In line based text files, such
+    /// as COLMAP or TUM lists, they are the lines of the file.
+    #[error("invalid data in row {row} of element '{element}': {message}")]
+    Payload {
+        element: String,
+        row: usize,
+        message: String,
+    },
+    #[error("file ends in row {row}, before all {count} rows of element '{element}' were read")]
+    Truncated {
+        element: String,
+        row: usize,
+        count: usize,
+    },
+    #[error("face {row} refers to vertex {index}, but there are only {vertex_count} vertices")]
+    FaceIndexOutOfRange {
+        row: usize,
+        index: i64,
+        vertex_count: usize,
+    },
+    #[error("face {row} has negative vertex index {index}")]
+    NegativeFaceIndex { row: usize, index: i64 },
+    #[error("the format of the file is unknown")]
+    UnknownFormat,
+    #[error("import was cancelled")]
+    Cancelled,
+    #[error("{0}")]
+    Image(#[from] image::ImageError),
+    #[error("{0}")]
+    Tiff(#[from] tiff::TiffError),
+    #[error("{0}")]
+    Las(Box<::las::Error>),
+    #[error("{0}")]
+    Gltf(#[from] ::gltf::Error),
+    #[error("{0}")]
+    E57(#[from] ::e57::Error),
+}
+
+impl From<::las::Error> for ImportErrorKind {
+    fn from(error: ::las::Error) -> Self {
+        // boxed, since it is much larger than the other variants
+        ImportErrorKind::Las(Box::new(error))
+    }
+}
+
+/// Imports a file, reporting how much of it was read and stopping early when the import is
+/// cancelled.
+pub fn import(
+    path: &Path,
+    options: &ImportOptions,
+    progress: &Progress,
+) -> Result<Import, ImportError> {
+    import_file(path, options, progress).map_err(|kind| ImportError {
+        path: path.to_owned(),
+        // readers turn the failed read into their own kind of error
+        kind: if progress.is_cancelled() {
+            ImportErrorKind::Cancelled
+        } else {
+            kind
+        },
+    })
+}
+
+fn import_file(
+    path: &Path,
+    options: &ImportOptions,
+    progress: &Progress,
+) -> Result<Import, ImportErrorKind> {
+    if path == Path::new(STDIN_PATH) {
+        let reader = ProgressReader::new(io::stdin(), 0, progress);
+        return import_stream(reader, path, Path::new(""), options);
+    }
+
+    // a COLMAP model can be opened through its directory or any of its files
+    if let Some(dir) = colmap::model_dir(path) {
+        return Ok(Import::Ply(colmap::import_colmap(dir, progress)?));
+    }
+    if path.is_dir() && tum::is_tum_dataset(path) {
+        return Ok(Import::Rgbd(tum::import_tum(
+            path,
+            options.depth.as_ref(),
+            progress,
+        )?));
+    }
+    // a directory of lidar scans is played back as a sequence
+    if path.is_dir() {
+        let frames = kitti::scan_files(path, progress)?;
+        if !frames.is_empty() {
+            return Ok(Import::Sequence(Sequence { frames }));
         }
     }
 
-    fn set_property(&mut self, key: String, property: ply::Property) {
-        match (key.as_ref(), property) {
-            ("x", ply::Property::Double(v)) => self.position[0] = v as f32,
-            ("y", ply::Property::Double(v)) => self.position[1] = v as f32,
-            ("z", ply::Property::Double(v)) => self.position[2] = v as f32,
-            ("red", ply::Property::UChar(v)) => self.color[0] = v as f32 / 255.,
-            ("green", ply::Property::UChar(v)) => self.color[1] = v as f32 / 255.,
-            ("blue", ply::Property::UChar(v)) => self.color[2] = v as f32 / 255.,
-            (k, _) => panic!("Unexpected key/value combination: key: {}", k),
+    let f = File::open(path)?;
+    let metadata = f.metadata()?;
+    // formats that refer to other files look for them next to the imported one
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    // named pipes have no size and can't seek
+    if !metadata.is_file() {
+        let reader = ProgressReader::new(f, 0, progress);
+        return import_stream(reader, path, base_dir, options);
+    }
+    let total = metadata.len();
+    let mut buf_read = BufReader::new(ProgressReader::new(f, total, progress));
+    if Compression::detect(buf_read.fill_buf()?).is_some() {
+        return import_stream(buf_read, path, base_dir, options);
+    }
+
+    let extension = path.extension().and_then(std::ffi::OsStr::to_str);
+    let format = Format::detect(buf_read.fill_buf()?, Some(total), extension)
+        .ok_or(ImportErrorKind::UnknownFormat)?;
+    read_seekable(buf_read, format, base_dir, options)
+}
+
+/// Imports a stream that can only be read once, such as the standard input, decompressing it
+/// first if it is compressed. The extension of `name` is used as a hint for the format.
+fn import_stream(
+    reader: impl Read,
+    name: &Path,
+    base_dir: &Path,
+    options: &ImportOptions,
+) -> Result<Import, ImportErrorKind> {
+    let (header, reader) = peek(reader)?;
+    match Compression::detect(&header) {
+        Some(compression) => {
+            // the extension before the one of the compression, such as the `ply` of `scan.ply.gz`
+            let extension = name
+                .file_stem()
+                .and_then(|stem| Path::new(stem).extension())
+                .and_then(std::ffi::OsStr::to_str);
+            let (header, decoder) = peek(compression.decoder(reader)?)?;
+            let format =
+                Format::detect(&header, None, extension).ok_or(ImportErrorKind::UnknownFormat)?;
+            read_stream(decoder, format, base_dir, options)
+        }
+        None => {
+            let extension = name.extension().and_then(std::ffi::OsStr::to_str);
+            let format =
+                Format::detect(&header, None, extension).ok_or(ImportErrorKind::UnknownFormat)?;
+            read_stream(reader, format, base_dir, options)
         }
     }
 }
 
-impl ply::PropertyAccess for Face {
-    fn new() -> Self {
-        Face {
-            vertex_index: Vec::new(),
+/// Reads the first bytes of a stream to detect its format, and returns them together with a
+/// reader of the whole stream.
+///
+/// Pipes hand out their data in the pieces it was written in, so a single read may not be
+/// enough.
+fn peek(mut reader: impl Read) -> io::Result<(Vec<u8>, impl BufRead)> {
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    reader
+        .by_ref()
+        .take(HEADER_SIZE as u64)
+        .read_to_end(&mut header)?;
+    Ok((
+        header.clone(),
+        BufReader::new(Cursor::new(header).chain(reader)),
+    ))
+}
+
+/// Reads a file in `format` from a reader that can only be read once, such as a decompressor.
+///
+/// The formats that jump around in their files are read into memory first.
+fn read_stream(
+    mut reader: impl BufRead,
+    format: Format,
+    base_dir: &Path,
+    options: &ImportOptions,
+) -> Result<Import, ImportErrorKind> {
+    match format {
+        Format::E57 | Format::Las | Format::Image | Format::Tiff => {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            read_seekable(Cursor::new(bytes), format, base_dir, options)
         }
+        Format::Ply => Ok(Import::Ply(ply::import_ply(reader)?)),
+        Format::Pcd => Ok(Import::Ply(pcd::import_pcd(reader)?)),
+        Format::Obj => Ok(Import::Ply(obj::import_obj(reader, base_dir)?)),
+        Format::KittiBin => Ok(Import::Ply(kitti::import_kitti_bin(reader)?)),
+        Format::Bundler => Ok(Import::Ply(bundler::import_bundler(reader, base_dir)?)),
+        Format::Nvm => Ok(Import::Ply(nvm::import_nvm(reader)?)),
+        Format::Stl => Ok(Import::Ply(stl::import_stl(reader)?)),
+        Format::Gltf => Ok(Import::Ply(gltf::import_gltf(reader, base_dir)?)),
+        Format::Text => Ok(Import::Ply(xyz::import_xyz(reader, &options.text)?)),
     }
+}
 
-    fn set_property(&mut self, key: String, property: ply::Property) {
-        match (key.as_ref(), property) {
-            ("vertex_index", ply::Property::ListInt(vec)) => self.vertex_index = vec,
-            (k, _) => panic!("Face: Unexpected key/value combination: key: {}", k),
+/// Reads a file in `format` from a reader that can seek, such as the file itself.
+fn read_seekable<R: BufRead + Seek + Send + Debug>(
+    mut reader: R,
+    format: Format,
+    base_dir: &Path,
+    options: &ImportOptions,
+) -> Result<Import, ImportErrorKind> {
+    match format {
+        Format::E57 => Ok(Import::Ply(e57::import_e57(reader)?)),
+        Format::Las => Ok(Import::Ply(las::import_las(reader)?)),
+        Format::Image | Format::Tiff => {
+            let is_tiff = format == Format::Tiff;
+            if let Some(depth) = &options.depth {
+                if depth::is_depth_image(&mut reader, is_tiff)? {
+                    return Ok(Import::Ply(depth::import_depth(reader, is_tiff, depth)?));
+                }
+            }
+            // let img = image::load_from_memory(buf_read)?;
+            let img = image::io::Reader::new(reader)
+                .with_guessed_format()?
+                .decode()?;
+            Ok(Import::Image(img))
         }
+        _ => read_stream(reader, format, base_dir, options),
     }
 }
 
 /// Calculates the average position of all vertices provided in the list
-pub fn avg_vertex_position(vertices: &[Vertex]) -> Point3<f32> {
+pub fn avg_vertex_position<'a>(vertices: impl IntoIterator<Item = &'a Vertex>) -> Point3<f32> {
     let mut center = Point3::<f64>::new(0.0, 0.0, 0.0);
-    let length = vertices.len() as f64;
+    let mut length = 0.0;
     for v in vertices {
         center.coords += Vector3::from(v.position).map(|x| x as f64);
+        length += 1.0;
     }
 
     center.coords /= length;
@@ -163,13 +582,78 @@ pub fn avg_vertex_position(vertices: &[Vertex]) -> Point3<f32> {
 }
 
 /// Calculates the average distance of all vertices to the average vertex position
-pub fn avg_vertex_distance(avg_vertex_position: Point3<f32>, vertices: &[Vertex]) -> f32 {
+pub fn avg_vertex_distance<'a>(
+    avg_vertex_position: Point3<f32>,
+    vertices: impl IntoIterator<Item = &'a Vertex>,
+) -> f32 {
     let mut sum = 0.0;
-    let length = vertices.len() as f32;
+    let mut length = 0.0;
     for v in vertices {
         let pos = Point3::<f32>::from(v.position);
         sum += distance(&avg_vertex_position, &pos);
+        length += 1.0;
     }
 
     sum / length
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A reader that hands out its data in small pieces, like a pipe that is written to slowly.
+    struct Pipe<R>(R);
+
+    impl<R: Read> Read for Pipe<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(7);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    /// Points as text that is longer than the header that formats are detected from.
+    fn long_text() -> String {
+        (0..2000)
+            .map(|i| format!("{} {} {}\n", i, i + 1, i + 2))
+            .collect()
+    }
+
+    fn import_points(reader: impl Read, name: &str) -> PlyData {
+        let options = ImportOptions::default();
+        match import_stream(reader, Path::new(name), Path::new(""), &options).unwrap() {
+            Import::Ply(ply_data) => ply_data,
+            _ => panic!("the stream was not imported as geometry"),
+        }
+    }
+
+    #[test]
+    fn stream_from_pipe() {
+        let text = long_text();
+        let ply_data = import_points(Pipe(text.as_bytes()), STDIN_PATH);
+        assert_eq!(ply_data.point_vertices.len(), 2000);
+        assert_eq!(
+            ply_data.point_vertices[1999].position,
+            [1999.0, 2000.0, 2001.0]
+        );
+    }
+
+    #[test]
+    fn compressed_stream() {
+        let text = long_text();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let gzip = encoder.finish().unwrap();
+        let ply_data = import_points(Pipe(&gzip[..]), STDIN_PATH);
+        assert_eq!(ply_data.point_vertices.len(), 2000);
+
+        // KITTI scans have no signature, so the name of a stream is the only hint
+        let scan = [1.0f32, 2.0, 3.0, 0.5]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let zstd = zstd::encode_all(&scan[..], 0).unwrap();
+        let ply_data = import_points(Pipe(&zstd[..]), "000000.bin.zst");
+        assert_eq!(ply_data.point_vertices[0].position, [1.0, 2.0, 3.0]);
+    }
+}
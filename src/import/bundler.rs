@@ -0,0 +1,241 @@
+//! Reader for Bundler `.out` reconstructions.
+
+use super::{CameraView, ImportErrorKind, Intrinsics, PlyData, ScalarField, MAX_RESERVED_ROWS};
+use crate::points::Vertex;
+use nalgebra::{IsometryMatrix3, Matrix3, Rotation3, Translation3, Vector3};
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+    str::FromStr,
+};
+
+/// Imports the points and cameras of a bundle file.
+///
+/// Bundler keeps the image names in a separate `list.txt`, which is looked for in `base_dir` and
+/// its parent.
+pub fn import_bundler(mut reader: impl Read, base_dir: &Path) -> Result<PlyData, ImportErrorKind> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    if !text.starts_with("# Bundle file") {
+        return Err(ImportErrorKind::Header(
+            "missing '# Bundle file' line".into(),
+        ));
+    }
+    let header_end = text.find('\n').unwrap_or(text.len());
+    let mut tokens = Tokens::new(text, header_end);
+
+    let camera_count: usize = tokens.next("cameras")?;
+    let point_count: usize = tokens.next("points")?;
+    let names = image_names(base_dir)?;
+
+    // Bundler cameras look along negative z with y pointing up, flipping both axes turns them
+    // into the convention of `CameraView`.
+    let flip = Matrix3::from_diagonal(&Vector3::new(1.0, -1.0, -1.0));
+    let mut views = Vec::new();
+    for i in 0..camera_count {
+        tokens.row = i;
+        let [f, k1, k2] = tokens.next_array::<f64, 3>("cameras")?;
+        let rotation = tokens.next_array::<f64, 9>("cameras")?;
+        let translation = tokens.next_array::<f64, 3>("cameras")?;
+        // cameras that could not be registered are all zeros
+        if f == 0.0 {
+            continue;
+        }
+        let rotation = flip * Matrix3::from_row_slice(&rotation);
+        let translation = flip * Vector3::from(translation);
+        views.push(CameraView {
+            name: names
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| format!("camera {}", i)),
+            world_to_camera: IsometryMatrix3::from_parts(
+                Translation3::from(translation),
+                Rotation3::from_matrix_unchecked(rotation),
+            )
+            .cast(),
+            // the principal point is the center of the image, whose size is unknown
+            intrinsics: Intrinsics {
+                model: "BUNDLER".into(),
+                width: 0,
+                height: 0,
+                fx: f as f32,
+                fy: f as f32,
+                cx: 0.0,
+                cy: 0.0,
+                distortion: vec![k1 as f32, k2 as f32],
+            },
+        });
+    }
+
+    let mut point_vertices = Vec::with_capacity(point_count.min(MAX_RESERVED_ROWS));
+    let mut track_lengths = Vec::with_capacity(point_count.min(MAX_RESERVED_ROWS));
+    for row in 0..point_count {
+        tokens.row = row;
+        let position = tokens.next_array::<f32, 3>("points")?;
+        let color = tokens.next_array::<f32, 3>("points")?;
+        let track_length: usize = tokens.next("points")?;
+        // every entry of the view list is a camera index, a key point index and its position
+        tokens.skip(track_length * 4, "points")?;
+        point_vertices.push(Vertex {
+            position,
+            _padding0: [0; 4],
+            color: color.map(|c| c / 255.0),
+            _padding1: [0; 4],
+        });
+        track_lengths.push(track_length as f32);
+    }
+
+    Ok(PlyData {
+        point_vertices,
+        scalar_fields: vec![ScalarField {
+            name: "track_length".into(),
+            point_values: track_lengths,
+            face_values: Vec::new(),
+        }],
+        views,
+        ..Default::default()
+    })
+}
+
+/// Reads the image names from the first column of `list.txt`, if there is one.
+fn image_names(base_dir: &Path) -> io::Result<Vec<String>> {
+    let list = [Some(base_dir), base_dir.parent()]
+        .iter()
+        .flatten()
+        .map(|dir| dir.join("list.txt"))
+        .find(|path| path.is_file());
+    match list {
+        Some(list) => Ok(fs::read_to_string(list)?
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_owned)
+            .collect()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The whitespace separated values of a reconstruction file, whose line breaks don't matter.
+pub(super) struct Tokens {
+    text: String,
+    position: usize,
+    /// Row of the element that is being read, which errors are reported in.
+    pub(super) row: usize,
+}
+
+impl Tokens {
+    /// Splits `text` into tokens, starting at byte `start`.
+    pub(super) fn new(text: String, start: usize) -> Tokens {
+        Tokens {
+            text,
+            position: start,
+            row: 0,
+        }
+    }
+
+    pub(super) fn next_str(&mut self, element: &str) -> Result<&str, ImportErrorKind> {
+        let rest = &self.text[self.position..];
+        let start =
+            rest.find(|c: char| !c.is_whitespace())
+                .ok_or_else(|| ImportErrorKind::Payload {
+                    element: element.to_owned(),
+                    row: self.row,
+                    message: "file ends before all values were read".into(),
+                })?;
+        let len = rest[start..]
+            .find(char::is_whitespace)
+            .unwrap_or(rest.len() - start);
+        self.position += start + len;
+        Ok(&self.text[self.position - len..self.position])
+    }
+
+    pub(super) fn next<T: FromStr>(&mut self, element: &str) -> Result<T, ImportErrorKind> {
+        let row = self.row;
+        let token = self.next_str(element)?;
+        token.parse().map_err(|_| ImportErrorKind::Payload {
+            element: element.to_owned(),
+            row,
+            message: format!("invalid value '{}'", token),
+        })
+    }
+
+    pub(super) fn next_array<T: FromStr + Default + Copy, const N: usize>(
+        &mut self,
+        element: &str,
+    ) -> Result<[T; N], ImportErrorKind> {
+        let mut values = [T::default(); N];
+        for value in &mut values {
+            *value = self.next(element)?;
+        }
+        Ok(values)
+    }
+
+    pub(super) fn skip(&mut self, count: usize, element: &str) -> Result<(), ImportErrorKind> {
+        for _ in 0..count {
+            self.next_str(element)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Point3;
+
+    /// Two cameras, the first of which wasn't registered, and two points.
+    const BUNDLE: &str = "# Bundle file v0.3
+2 2
+0 0 0
+0 0 0
+0 0 0
+0 0 0
+0 0 0
+500 0.1 0.2
+1 0 0
+0 1 0
+0 0 1
+0 0 -5
+1 2 3
+255 0 0
+2 1 10 1.5 2.5 0 3 -1.5 0.5
+4 5 6
+0 255 0
+0
+";
+
+    #[test]
+    fn cameras_and_points() {
+        let dir = std::env::temp_dir().join(format!("ennona-bundler-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let ply_data = import_bundler(BUNDLE.as_bytes(), &dir).unwrap();
+        assert_eq!(ply_data.views.len(), 1);
+        assert_eq!(ply_data.views[0].name, "camera 1");
+        assert_eq!(ply_data.views[0].intrinsics.fx, 500.0);
+        assert_eq!(ply_data.views[0].intrinsics.distortion, [0.1, 0.2]);
+        // a point in front of a Bundler camera, along negative z, ends up along positive z
+        let point = ply_data.views[0].world_to_camera * Point3::new(0.0, 1.0, 0.0);
+        assert_eq!(point, Point3::new(0.0, -1.0, 5.0));
+
+        assert_eq!(ply_data.point_vertices[0].position, [1.0, 2.0, 3.0]);
+        assert_eq!(ply_data.point_vertices[1].color, [0.0, 1.0, 0.0]);
+        assert_eq!(ply_data.scalar_fields[0].point_values, [2.0, 0.0]);
+
+        // image names are taken from the list next to the bundle
+        fs::write(dir.join("list.txt"), "unused.jpg 0 100\nimage.jpg\n").unwrap();
+        let ply_data = import_bundler(BUNDLE.as_bytes(), &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(ply_data.views[0].name, "image.jpg");
+    }
+
+    #[test]
+    fn truncated_points() {
+        let bundle = &BUNDLE[..BUNDLE.len() - 10];
+        match import_bundler(bundle.as_bytes(), Path::new("")) {
+            Err(ImportErrorKind::Payload { element, row, .. }) => {
+                assert_eq!((element.as_str(), row), ("points", 1));
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,628 @@
+//! Reader for COLMAP sparse models, stored either as `cameras.bin`, `images.bin` and
+//! `points3D.bin` or as their `.txt` counterparts.
+
+use super::{
+    progress::{Progress, ProgressReader},
+    CameraView, ImportErrorKind, Intrinsics, PlyData, ScalarField, MAX_RESERVED_ROWS,
+};
+use crate::points::Vertex;
+use byteorder::{LittleEndian, ReadBytesExt};
+use nalgebra::{IsometryMatrix3, Quaternion, Translation3, UnitQuaternion};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    str::SplitWhitespace,
+};
+
+/// The files that make up a COLMAP model, without their extension.
+const MODEL_FILES: [&str; 3] = ["cameras", "images", "points3D"];
+
+/// Returns if `dir` contains a binary or text COLMAP model.
+pub fn is_colmap_model(dir: &Path) -> bool {
+    MODEL_FILES.iter().all(|name| {
+        dir.join(format!("{}.bin", name)).is_file() || dir.join(format!("{}.txt", name)).is_file()
+    })
+}
+
+/// Returns the directory of the COLMAP model that `path` opens, which is either the directory
+/// itself or one of the model files in it.
+///
+/// Other files next to a model, such as a dense point cloud, are not part of it.
+pub fn model_dir(path: &Path) -> Option<&Path> {
+    let dir = if path.is_dir() {
+        path
+    } else {
+        let stem = path.file_stem()?.to_str()?;
+        let extension = path.extension()?.to_str()?;
+        if !MODEL_FILES.contains(&stem) || !matches!(extension, "bin" | "txt") {
+            return None;
+        }
+        path.parent()?
+    };
+    Some(dir).filter(|dir| is_colmap_model(dir))
+}
+
+/// Imports the points and registered camera views of the COLMAP model in `dir`.
+///
+/// The binary files are preferred if both variants are present. Progress is reported over the
+/// files of the model together.
+pub fn import_colmap(dir: &Path, progress: &Progress) -> Result<PlyData, ImportErrorKind> {
+    let [cameras, images, points] = MODEL_FILES.map(|name| {
+        let binary = dir.join(format!("{}.bin", name));
+        if binary.is_file() {
+            (binary, true)
+        } else {
+            (dir.join(format!("{}.txt", name)), false)
+        }
+    });
+    let total = [&cameras, &images, &points]
+        .iter()
+        .map(|(path, _)| path.metadata().map(|metadata| metadata.len()))
+        .sum::<io::Result<u64>>()?;
+    let mut offset = 0;
+    let mut open = |(path, is_binary): &(PathBuf, bool)| {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let reader = ProgressReader::with_offset(file, offset, total, progress);
+        offset += size;
+        Ok::<_, io::Error>((BufReader::new(reader), *is_binary))
+    };
+
+    let cameras = match open(&cameras)? {
+        (reader, true) => read_cameras_binary(reader),
+        (reader, false) => read_cameras_text(reader),
+    }?;
+    let images = match open(&images)? {
+        (reader, true) => read_images_binary(reader),
+        (reader, false) => read_images_text(reader),
+    }?;
+    let points = match open(&points)? {
+        (reader, true) => read_points_binary(reader),
+        (reader, false) => read_points_text(reader),
+    }?;
+
+    let views =
+        images
+            .into_iter()
+            .enumerate()
+            .map(|(row, image)| {
+                let intrinsics = cameras.get(&image.camera_id).cloned().ok_or_else(|| {
+                    ImportErrorKind::Payload {
+                        element: "images".into(),
+                        row,
+                        message: format!(
+                            "image '{}' refers to unknown camera {}",
+                            image.name, image.camera_id
+                        ),
+                    }
+                })?;
+                Ok(CameraView {
+                    name: image.name,
+                    world_to_camera: image.world_to_camera,
+                    intrinsics,
+                })
+            })
+            .collect::<Result<Vec<_>, ImportErrorKind>>()?;
+
+    Ok(PlyData {
+        point_vertices: points.vertices,
+        scalar_fields: vec![
+            ScalarField {
+                name: "error".into(),
+                point_values: points.errors,
+                face_values: Vec::new(),
+            },
+            ScalarField {
+                name: "track_length".into(),
+                point_values: points.track_lengths,
+                face_values: Vec::new(),
+            },
+        ],
+        views,
+        ..Default::default()
+    })
+}
+
+/// An entry of the `images` file.
+struct Image {
+    name: String,
+    camera_id: u32,
+    world_to_camera: IsometryMatrix3<f32>,
+}
+
+/// The contents of the `points3D` file.
+#[derive(Default)]
+struct Points {
+    vertices: Vec<Vertex>,
+    errors: Vec<f32>,
+    track_lengths: Vec<f32>,
+}
+
+impl Points {
+    fn with_capacity(capacity: usize) -> Points {
+        Points {
+            vertices: Vec::with_capacity(capacity),
+            errors: Vec::with_capacity(capacity),
+            track_lengths: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn push(&mut self, position: [f64; 3], color: [u8; 3], error: f64, track_length: usize) {
+        self.vertices.push(Vertex {
+            position: [position[0] as f32, position[1] as f32, position[2] as f32],
+            _padding0: [0; 4],
+            color: [
+                color[0] as f32 / 255.,
+                color[1] as f32 / 255.,
+                color[2] as f32 / 255.,
+            ],
+            _padding1: [0; 4],
+        });
+        self.errors.push(error as f32);
+        self.track_lengths.push(track_length as f32);
+    }
+}
+
+/// Returns the name of a COLMAP camera model, its number of parameters and if it only has a
+/// single focal length.
+fn camera_model(id: i32) -> Option<(&'static str, usize, bool)> {
+    Some(match id {
+        0 => ("SIMPLE_PINHOLE", 3, true),
+        1 => ("PINHOLE", 4, false),
+        2 => ("SIMPLE_RADIAL", 4, true),
+        3 => ("RADIAL", 5, true),
+        4 => ("OPENCV", 8, false),
+        5 => ("OPENCV_FISHEYE", 8, false),
+        6 => ("FULL_OPENCV", 12, false),
+        7 => ("FOV", 5, false),
+        8 => ("SIMPLE_RADIAL_FISHEYE", 4, true),
+        9 => ("RADIAL_FISHEYE", 5, true),
+        10 => ("THIN_PRISM_FISHEYE", 12, false),
+        _ => return None,
+    })
+}
+
+/// Builds the intrinsics from the parameters of a camera model.
+///
+/// The focal length and principal point always come first, all following parameters describe
+/// the lens distortion.
+fn intrinsics(
+    model: &str,
+    single_focal: bool,
+    width: u64,
+    height: u64,
+    params: &[f64],
+) -> Intrinsics {
+    let (fx, fy, rest) = if single_focal {
+        (params[0], params[0], &params[1..])
+    } else {
+        (params[0], params[1], &params[2..])
+    };
+    Intrinsics {
+        model: model.to_owned(),
+        width: width as u32,
+        height: height as u32,
+        fx: fx as f32,
+        fy: fy as f32,
+        cx: rest[0] as f32,
+        cy: rest[1] as f32,
+        distortion: rest[2..].iter().map(|&p| p as f32).collect(),
+    }
+}
+
+/// Builds a world to camera transform from a COLMAP quaternion `[w, x, y, z]` and translation.
+fn world_to_camera(q: [f64; 4], t: [f64; 3]) -> IsometryMatrix3<f32> {
+    let rotation = UnitQuaternion::from_quaternion(Quaternion::new(q[0], q[1], q[2], q[3]));
+    IsometryMatrix3::from_parts(
+        Translation3::new(t[0], t[1], t[2]),
+        rotation.to_rotation_matrix(),
+    )
+    .cast()
+}
+
+/// Maps an I/O error from reading `row` of a binary file, reporting a premature end of the file
+/// as truncation of `element`.
+fn binary_error(
+    element: &str,
+    row: usize,
+    count: usize,
+) -> impl Fn(io::Error) -> ImportErrorKind + '_ {
+    move |e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => ImportErrorKind::Truncated {
+            element: element.to_owned(),
+            row,
+            count,
+        },
+        _ => ImportErrorKind::Io(e),
+    }
+}
+
+fn read_cameras_binary(
+    mut reader: impl BufRead,
+) -> Result<HashMap<u32, Intrinsics>, ImportErrorKind> {
+    let count = reader
+        .read_u64::<LittleEndian>()
+        .map_err(binary_error("cameras", 0, 0))? as usize;
+    let mut cameras = HashMap::new();
+    for row in 0..count {
+        let eof = binary_error("cameras", row, count);
+        let mut read_header = || -> io::Result<_> {
+            Ok((
+                reader.read_u32::<LittleEndian>()?,
+                reader.read_i32::<LittleEndian>()?,
+                reader.read_u64::<LittleEndian>()?,
+                reader.read_u64::<LittleEndian>()?,
+            ))
+        };
+        let (camera_id, model_id, width, height) = read_header().map_err(&eof)?;
+        let (model, num_params, single_focal) =
+            camera_model(model_id).ok_or_else(|| ImportErrorKind::Payload {
+                element: "cameras".into(),
+                row,
+                message: format!("unknown camera model {}", model_id),
+            })?;
+        let mut params = vec![0.0; num_params];
+        reader
+            .read_f64_into::<LittleEndian>(&mut params)
+            .map_err(&eof)?;
+        cameras.insert(
+            camera_id,
+            intrinsics(model, single_focal, width, height, &params),
+        );
+    }
+    Ok(cameras)
+}
+
+fn read_images_binary(mut reader: impl BufRead) -> Result<Vec<Image>, ImportErrorKind> {
+    let count = reader
+        .read_u64::<LittleEndian>()
+        .map_err(binary_error("images", 0, 0))? as usize;
+    let mut read_image = || -> io::Result<Image> {
+        let _image_id = reader.read_u32::<LittleEndian>()?;
+        let mut q = [0.0; 4];
+        reader.read_f64_into::<LittleEndian>(&mut q)?;
+        let mut t = [0.0; 3];
+        reader.read_f64_into::<LittleEndian>(&mut t)?;
+        let camera_id = reader.read_u32::<LittleEndian>()?;
+        let mut name = Vec::new();
+        reader.read_until(0, &mut name)?;
+        if name.pop() != Some(0) {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        // the 2D observations are not needed to display the view
+        let num_points2d = reader.read_u64::<LittleEndian>()?;
+        for _ in 0..num_points2d {
+            reader.read_f64::<LittleEndian>()?;
+            reader.read_f64::<LittleEndian>()?;
+            reader.read_i64::<LittleEndian>()?;
+        }
+        Ok(Image {
+            name: String::from_utf8_lossy(&name).into_owned(),
+            camera_id,
+            world_to_camera: world_to_camera(q, t),
+        })
+    };
+    let mut images = Vec::with_capacity(count.min(MAX_RESERVED_ROWS));
+    for row in 0..count {
+        images.push(read_image().map_err(binary_error("images", row, count))?);
+    }
+    Ok(images)
+}
+
+fn read_points_binary(mut reader: impl BufRead) -> Result<Points, ImportErrorKind> {
+    let count = reader
+        .read_u64::<LittleEndian>()
+        .map_err(binary_error("points3D", 0, 0))? as usize;
+    let mut points = Points::with_capacity(count.min(MAX_RESERVED_ROWS));
+    let mut read_point = |points: &mut Points| -> io::Result<()> {
+        let _point_id = reader.read_u64::<LittleEndian>()?;
+        let mut position = [0.0; 3];
+        reader.read_f64_into::<LittleEndian>(&mut position)?;
+        let mut color = [0; 3];
+        reader.read_exact(&mut color)?;
+        let error = reader.read_f64::<LittleEndian>()?;
+        let track_length = reader.read_u64::<LittleEndian>()? as usize;
+        // each track element is an image id and a 2D point index
+        for _ in 0..track_length {
+            reader.read_u64::<LittleEndian>()?;
+        }
+        points.push(position, color, error, track_length);
+        Ok(())
+    };
+    for row in 0..count {
+        read_point(&mut points).map_err(binary_error("points3D", row, count))?;
+    }
+    Ok(points)
+}
+
+/// Iterates over the data lines of a text file, skipping comments.
+///
+/// Empty lines are kept, since they are meaningful in `images.txt`. Every line comes with its row
+/// in the file, counted from zero.
+fn data_lines(
+    reader: impl BufRead,
+) -> impl Iterator<Item = Result<(usize, String), ImportErrorKind>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.starts_with('#')))
+        .map(|(row, line)| Ok((row, line?)))
+}
+
+/// Parses the next whitespace separated value of a line in a text file.
+fn next_value<T: std::str::FromStr>(
+    values: &mut SplitWhitespace<'_>,
+    element: &str,
+    row: usize,
+) -> Result<T, ImportErrorKind> {
+    values
+        .next()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| ImportErrorKind::Payload {
+            element: element.to_owned(),
+            row,
+            message: "invalid or missing value".into(),
+        })
+}
+
+fn read_cameras_text(reader: impl BufRead) -> Result<HashMap<u32, Intrinsics>, ImportErrorKind> {
+    let mut cameras = HashMap::new();
+    for line in data_lines(reader) {
+        let (row, line) = line?;
+        let mut values = line.split_whitespace();
+        let camera_id = match values.next() {
+            Some(camera_id) => camera_id,
+            None => continue,
+        };
+        let camera_id = camera_id.parse().map_err(|_| ImportErrorKind::Payload {
+            element: "cameras".into(),
+            row,
+            message: "invalid camera id".into(),
+        })?;
+        let model_name = values.next().unwrap_or_default();
+        let (model, num_params, single_focal) = (0..=10)
+            .filter_map(camera_model)
+            .find(|(model, ..)| *model == model_name)
+            .ok_or_else(|| ImportErrorKind::Payload {
+                element: "cameras".into(),
+                row,
+                message: format!("unknown camera model '{}'", model_name),
+            })?;
+        let width = next_value(&mut values, "cameras", row)?;
+        let height = next_value(&mut values, "cameras", row)?;
+        let params = (0..num_params)
+            .map(|_| next_value(&mut values, "cameras", row))
+            .collect::<Result<Vec<f64>, _>>()?;
+        cameras.insert(
+            camera_id,
+            intrinsics(model, single_focal, width, height, &params),
+        );
+    }
+    Ok(cameras)
+}
+
+fn read_images_text(reader: impl BufRead) -> Result<Vec<Image>, ImportErrorKind> {
+    let mut images = Vec::new();
+    let mut lines = data_lines(reader);
+    while let Some(line) = lines.next() {
+        let (row, line) = line?;
+        let mut values = line.split_whitespace();
+        if values.next().is_none() {
+            continue;
+        }
+        let mut q = [0.0; 4];
+        for v in &mut q {
+            *v = next_value(&mut values, "images", row)?;
+        }
+        let mut t = [0.0; 3];
+        for v in &mut t {
+            *v = next_value(&mut values, "images", row)?;
+        }
+        let camera_id = next_value(&mut values, "images", row)?;
+        // image names may contain spaces, so the rest of the line is the name
+        let name = values.collect::<Vec<_>>().join(" ");
+        images.push(Image {
+            name,
+            camera_id,
+            world_to_camera: world_to_camera(q, t),
+        });
+        // every image is followed by a (possibly empty) line of 2D observations
+        lines.next().transpose()?;
+    }
+    Ok(images)
+}
+
+fn read_points_text(reader: impl BufRead) -> Result<Points, ImportErrorKind> {
+    let mut points = Points::default();
+    for line in data_lines(reader) {
+        let (row, line) = line?;
+        let mut values = line.split_whitespace();
+        if values.next().is_none() {
+            continue;
+        }
+        let mut position = [0.0; 3];
+        for v in &mut position {
+            *v = next_value(&mut values, "points3D", row)?;
+        }
+        let mut color = [0; 3];
+        for v in &mut color {
+            *v = next_value(&mut values, "points3D", row)?;
+        }
+        let error = next_value(&mut values, "points3D", row)?;
+        // the track is a list of image id and 2D point index pairs
+        let track_length = values.count() / 2;
+        points.push(position, color, error, track_length);
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    const CAMERAS_TXT: &str = "# Camera list
+1 PINHOLE 640 480 500 501 320 240
+2 SIMPLE_RADIAL 100 50 80 50 25 0.1
+";
+    const IMAGES_TXT: &str = "# Image list
+1 1 0 0 0 1 2 3 1 first image.jpg
+
+2 0.7071067811865476 0 0.7071067811865476 0 1 2 3 2 second.jpg
+10 20 -1 30 40 5
+";
+    const POINTS_TXT: &str = "# 3D point list
+1 1 2 3 255 0 0 0.5 1 0 2 1
+2 4 5 6 0 255 0 1.5 1 0
+";
+
+    fn cameras_bin() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u64::<LittleEndian>(2).unwrap();
+        for (id, model, size, params) in [
+            (1, 1, [640, 480], &[500.0, 501.0, 320.0, 240.0]),
+            (2, 2, [100, 50], &[80.0, 50.0, 25.0, 0.1]),
+        ] {
+            bytes.write_u32::<LittleEndian>(id).unwrap();
+            bytes.write_i32::<LittleEndian>(model).unwrap();
+            for value in size {
+                bytes.write_u64::<LittleEndian>(value).unwrap();
+            }
+            for &param in params {
+                bytes.write_f64::<LittleEndian>(param).unwrap();
+            }
+        }
+        bytes
+    }
+
+    fn images_bin() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u64::<LittleEndian>(2).unwrap();
+        for (id, q, camera_id, name, observations) in [
+            (1, [1.0, 0.0, 0.0, 0.0], 1, "first image.jpg", 0),
+            (
+                2,
+                [FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2, 0.0],
+                2,
+                "second.jpg",
+                2,
+            ),
+        ] {
+            bytes.write_u32::<LittleEndian>(id).unwrap();
+            for value in q.iter().chain(&[1.0, 2.0, 3.0]) {
+                bytes.write_f64::<LittleEndian>(*value).unwrap();
+            }
+            bytes.write_u32::<LittleEndian>(camera_id).unwrap();
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.push(0);
+            bytes.write_u64::<LittleEndian>(observations).unwrap();
+            for _ in 0..observations {
+                bytes.write_f64::<LittleEndian>(10.0).unwrap();
+                bytes.write_f64::<LittleEndian>(20.0).unwrap();
+                bytes.write_i64::<LittleEndian>(-1).unwrap();
+            }
+        }
+        bytes
+    }
+
+    fn points_bin() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u64::<LittleEndian>(2).unwrap();
+        for (id, position, color, error, track) in [
+            (1, [1.0, 2.0, 3.0], [255, 0, 0], 0.5, 2),
+            (2, [4.0, 5.0, 6.0], [0, 255, 0], 1.5, 1),
+        ] {
+            bytes.write_u64::<LittleEndian>(id).unwrap();
+            for value in position {
+                bytes.write_f64::<LittleEndian>(value).unwrap();
+            }
+            bytes.extend_from_slice(&color);
+            bytes.write_f64::<LittleEndian>(error).unwrap();
+            bytes.write_u64::<LittleEndian>(track).unwrap();
+            for _ in 0..track {
+                bytes.write_u32::<LittleEndian>(1).unwrap();
+                bytes.write_u32::<LittleEndian>(0).unwrap();
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn text_model() {
+        let cameras = read_cameras_text(CAMERAS_TXT.as_bytes()).unwrap();
+        assert_eq!(cameras[&1].model, "PINHOLE");
+        assert_eq!((cameras[&1].fx, cameras[&1].fy), (500.0, 501.0));
+        assert_eq!((cameras[&2].fx, cameras[&2].cx), (80.0, 50.0));
+        assert_eq!(cameras[&2].distortion, [0.1]);
+
+        let images = read_images_text(IMAGES_TXT.as_bytes()).unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].name, "first image.jpg");
+        assert_eq!(images[1].camera_id, 2);
+        assert_eq!(
+            images[0].world_to_camera.translation.vector,
+            nalgebra::Vector3::new(1.0, 2.0, 3.0)
+        );
+
+        let points = read_points_text(POINTS_TXT.as_bytes()).unwrap();
+        assert_eq!(points.vertices[1].position, [4.0, 5.0, 6.0]);
+        assert_eq!(points.vertices[0].color, [1.0, 0.0, 0.0]);
+        assert_eq!(points.errors, [0.5, 1.5]);
+        assert_eq!(points.track_lengths, [2.0, 1.0]);
+    }
+
+    #[test]
+    fn binary_model_matches_text() {
+        let text_cameras = read_cameras_text(CAMERAS_TXT.as_bytes()).unwrap();
+        let cameras = read_cameras_binary(&cameras_bin()[..]).unwrap();
+        for id in [1, 2] {
+            assert_eq!(cameras[&id].model, text_cameras[&id].model);
+            assert_eq!(cameras[&id].fx, text_cameras[&id].fx);
+            assert_eq!(cameras[&id].distortion, text_cameras[&id].distortion);
+        }
+
+        let text_images = read_images_text(IMAGES_TXT.as_bytes()).unwrap();
+        let images = read_images_binary(&images_bin()[..]).unwrap();
+        for (image, text_image) in images.iter().zip(&text_images) {
+            assert_eq!(image.name, text_image.name);
+            assert_eq!(image.camera_id, text_image.camera_id);
+            assert_eq!(image.world_to_camera, text_image.world_to_camera);
+        }
+
+        let text_points = read_points_text(POINTS_TXT.as_bytes()).unwrap();
+        let points = read_points_binary(&points_bin()[..]).unwrap();
+        for (vertex, text_vertex) in points.vertices.iter().zip(&text_points.vertices) {
+            assert_eq!(vertex.position, text_vertex.position);
+            assert_eq!(vertex.color, text_vertex.color);
+        }
+        assert_eq!(points.errors, text_points.errors);
+        assert_eq!(points.track_lengths, text_points.track_lengths);
+    }
+
+    #[test]
+    fn truncated_binary_points() {
+        let bytes = points_bin();
+        match read_points_binary(&bytes[..bytes.len() - 3]) {
+            Err(ImportErrorKind::Truncated {
+                element,
+                row,
+                count,
+            }) => {
+                assert_eq!((element.as_str(), row, count), ("points3D", 1, 2));
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn unknown_camera_model() {
+        let cameras = "1 PINHOLE 640 480 500 501 320 240\n2 SPHERICAL 1 1 1\n";
+        match read_cameras_text(cameras.as_bytes()) {
+            Err(ImportErrorKind::Payload { row, .. }) => assert_eq!(row, 1),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Detection and decompression of compressed files, such as `scan.ply.gz`.
+
+use std::io::{self, BufRead, Read};
+
+/// The compression formats that files are decompressed from before they are imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+const SIGNATURES: [(&[u8], Compression); 3] = [
+    (b"\x1f\x8b", Compression::Gzip),
+    (b"\x28\xb5\x2f\xfd", Compression::Zstd),
+    (b"\xfd7zXZ\0", Compression::Xz),
+];
+
+impl Compression {
+    /// Detects the compression of a stream that starts with `header`.
+    pub fn detect(header: &[u8]) -> Option<Compression> {
+        SIGNATURES
+            .iter()
+            .find(|(signature, _)| header.starts_with(signature))
+            .map(|&(_, compression)| compression)
+    }
+
+    /// Returns a reader of the decompressed content of `reader`.
+    ///
+    /// Streams of several concatenated parts, such as gzip files that were appended to each
+    /// other, are read to the end.
+    pub fn decoder<'a>(self, reader: impl BufRead + 'a) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+            Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+}
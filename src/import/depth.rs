@@ -0,0 +1,326 @@
+//! Back-projection of depth images, such as 16 bit PNGs or float TIFFs, into point clouds.
+
+use super::{CameraView, ImportErrorKind, Intrinsics, PlyData, ScalarField};
+use crate::points::Vertex;
+use image::{codecs::png::PngDecoder, GenericImageView, ImageDecoder, ImageFormat};
+use nalgebra::IsometryMatrix3;
+use std::{
+    fmt,
+    io::{BufRead, Seek, SeekFrom},
+    path::PathBuf,
+    str::FromStr,
+};
+use tiff::decoder::{Decoder, DecodingResult};
+
+/// Focal lengths and principal point of a pinhole camera, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pinhole {
+    pub fx: f32,
+    pub fy: f32,
+    pub cx: f32,
+    pub cy: f32,
+}
+
+impl FromStr for Pinhole {
+    type Err = String;
+
+    /// Parses `fx,fy,cx,cy`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        match values[..] {
+            [fx, fy, cx, cy] => Ok(Pinhole { fx, fy, cx, cy }),
+            _ => Err("expected four values, fx,fy,cx,cy".into()),
+        }
+    }
+}
+
+impl fmt::Display for Pinhole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.fx, self.fy, self.cx, self.cy)
+    }
+}
+
+/// Depth values per meter of single depth images when no other scale is given, as millimeters are
+/// the most common unit.
+pub const DEFAULT_DEPTH_SCALE: f32 = 1000.0;
+
+/// How images are turned into points when they are imported as depth.
+#[derive(Debug, Clone)]
+pub struct DepthOptions {
+    pub pinhole: Pinhole,
+    /// Depth values per meter, such as 1000 for millimeters. Without one, the default of the
+    /// format is used. Floating point images are already in meters and aren't scaled.
+    pub depth_scale: Option<f32>,
+    /// Image of the same size with the colors of the points.
+    pub color_image: Option<PathBuf>,
+}
+
+/// Depth values of an image, row by row.
+pub struct DepthMap {
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<f32>,
+    /// If the values are in meters rather than in units of the depth scale, as floating point
+    /// samples are.
+    pub in_meters: bool,
+}
+
+/// Returns if an image can hold depth, which takes 16 bit or floating point gray samples.
+///
+/// Other images, such as 8 bit color images, are imported as images even when depth is asked
+/// for. The reader is rewound to the start of the image.
+pub fn is_depth_image(
+    mut reader: impl BufRead + Seek,
+    is_tiff: bool,
+) -> Result<bool, ImportErrorKind> {
+    let is_depth = if is_tiff {
+        let mut decoder = Decoder::new(&mut reader)?;
+        matches!(decoder.colortype()?, tiff::ColorType::Gray(bits) if bits >= 16)
+    } else {
+        // PNG is the only other format with 16 bit samples
+        let format = image::io::Reader::new(&mut reader)
+            .with_guessed_format()?
+            .format();
+        format == Some(ImageFormat::Png)
+            && PngDecoder::new(&mut reader)?.color_type() == image::ColorType::L16
+    };
+    reader.seek(SeekFrom::Start(0))?;
+    Ok(is_depth)
+}
+
+/// Back-projects a depth image, coloring the points with the image in the options if it has one.
+pub fn import_depth(
+    reader: impl BufRead + Seek,
+    is_tiff: bool,
+    options: &DepthOptions,
+) -> Result<PlyData, ImportErrorKind> {
+    let depth = if is_tiff {
+        read_tiff_depth(reader)?
+    } else {
+        read_image_depth(reader)?
+    };
+    let color = match &options.color_image {
+        Some(path) => Some(image::open(path)?.to_rgb8()),
+        None => None,
+    };
+    back_project(&depth, options, color.as_ref())
+}
+
+/// Reads a depth map from a grayscale TIFF with integer or floating point samples.
+pub fn read_tiff_depth(reader: impl BufRead + Seek) -> Result<DepthMap, ImportErrorKind> {
+    let mut decoder = Decoder::new(reader)?;
+    let (width, height) = decoder.dimensions()?;
+    if !matches!(decoder.colortype()?, tiff::ColorType::Gray(_)) {
+        return Err(ImportErrorKind::Header(
+            "depth images have to be grayscale".into(),
+        ));
+    }
+    let (values, in_meters) = match decoder.read_image()? {
+        DecodingResult::U8(values) => (values.into_iter().map(f32::from).collect(), false),
+        DecodingResult::U16(values) => (values.into_iter().map(f32::from).collect(), false),
+        DecodingResult::U32(values) => (values.into_iter().map(|v| v as f32).collect(), false),
+        DecodingResult::U64(values) => (values.into_iter().map(|v| v as f32).collect(), false),
+        DecodingResult::F32(values) => (values, true),
+        DecodingResult::F64(values) => (values.into_iter().map(|v| v as f32).collect(), true),
+    };
+    Ok(DepthMap {
+        width,
+        height,
+        values,
+        in_meters,
+    })
+}
+
+/// Reads a depth map from any other image format, such as a 16 bit PNG.
+pub fn read_image_depth(reader: impl BufRead + Seek) -> Result<DepthMap, ImportErrorKind> {
+    let image = image::io::Reader::new(reader)
+        .with_guessed_format()?
+        .decode()?;
+    Ok(DepthMap {
+        width: image.width(),
+        height: image.height(),
+        values: image
+            .to_luma16()
+            .into_raw()
+            .into_iter()
+            .map(f32::from)
+            .collect(),
+        in_meters: false,
+    })
+}
+
+/// Turns every pixel with a valid depth into a point in the coordinates of the camera.
+pub fn back_project(
+    depth: &DepthMap,
+    options: &DepthOptions,
+    color: Option<&image::RgbImage>,
+) -> Result<PlyData, ImportErrorKind> {
+    if let Some(color) = color {
+        if color.dimensions() != (depth.width, depth.height) {
+            // the first row of the depth image without a matching row of color
+            let row = if color.width() == depth.width {
+                color.height().min(depth.height) as usize
+            } else {
+                0
+            };
+            return Err(ImportErrorKind::Payload {
+                element: "depth".into(),
+                row,
+                message: format!(
+                    "the color image is {}x{}, but the depth image is {}x{}",
+                    color.width(),
+                    color.height(),
+                    depth.width,
+                    depth.height
+                ),
+            });
+        }
+    }
+
+    let Pinhole { fx, fy, cx, cy } = options.pinhole;
+    let depth_scale = if depth.in_meters {
+        1.0
+    } else {
+        options.depth_scale.unwrap_or(DEFAULT_DEPTH_SCALE)
+    };
+    let mut point_vertices = Vec::new();
+    let mut depths = Vec::new();
+    for (i, &value) in depth.values.iter().enumerate() {
+        let z = value / depth_scale;
+        // zero marks pixels without a measurement
+        if !z.is_finite() || z <= 0.0 {
+            continue;
+        }
+        let u = (i % depth.width as usize) as u32;
+        let v = (i / depth.width as usize) as u32;
+        let color = color.map_or([1.0, 1.0, 1.0], |color| {
+            color.get_pixel(u, v).0.map(|c| c as f32 / 255.0)
+        });
+        point_vertices.push(Vertex {
+            position: [(u as f32 - cx) * z / fx, (v as f32 - cy) * z / fy, z],
+            _padding0: [0; 4],
+            color,
+            _padding1: [0; 4],
+        });
+        depths.push(z);
+    }
+
+    Ok(PlyData {
+        point_vertices,
+        scalar_fields: vec![ScalarField {
+            name: "depth".into(),
+            point_values: depths,
+            face_values: Vec::new(),
+        }],
+        // the points are in the coordinates of the camera that took the image
+        views: vec![CameraView {
+            name: "depth camera".into(),
+            world_to_camera: IsometryMatrix3::identity(),
+            intrinsics: Intrinsics {
+                model: "PINHOLE".into(),
+                width: depth.width,
+                height: depth.height,
+                fx,
+                fy,
+                cx,
+                cy,
+                distortion: Vec::new(),
+            },
+        }],
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{codecs::png::PngEncoder, DynamicImage, ImageEncoder};
+    use std::io::Cursor;
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    fn options() -> DepthOptions {
+        DepthOptions {
+            pinhole: "2,2,1,1".parse().unwrap(),
+            depth_scale: None,
+            color_image: None,
+        }
+    }
+
+    fn png(image: DynamicImage) -> Cursor<Vec<u8>> {
+        // `DynamicImage::write_to` writes 16 bit samples in the wrong byte order
+        let mut png = Vec::new();
+        let (width, height) = image.dimensions();
+        PngEncoder::new(&mut png)
+            .write_image(image.as_bytes(), width, height, image.color())
+            .unwrap();
+        Cursor::new(png)
+    }
+
+    fn depth_png() -> Cursor<Vec<u8>> {
+        // the left column has no measurement, the others are 1 and 2 meters away
+        let depth = image::ImageBuffer::from_fn(4, 2, |x, y| {
+            image::Luma([if x == 0 { 0 } else { 1000 * (y as u16 + 1) }])
+        });
+        png(DynamicImage::ImageLuma16(depth))
+    }
+
+    #[test]
+    fn back_project_png() {
+        let mut reader = depth_png();
+        assert!(is_depth_image(&mut reader, false).unwrap());
+        let ply_data = import_depth(reader, false, &options()).unwrap();
+        assert_eq!(ply_data.point_vertices.len(), 6);
+        assert_eq!(ply_data.point_vertices[0].position, [0.0, -0.5, 1.0]);
+        assert_eq!(ply_data.point_vertices[5].position, [2.0, 0.0, 2.0]);
+        assert_eq!(ply_data.scalar_fields[0].point_values[5], 2.0);
+        assert_eq!(ply_data.views[0].intrinsics.width, 4);
+    }
+
+    #[test]
+    fn color_image_has_to_match() {
+        let depth = read_image_depth(depth_png()).unwrap();
+        let color = image::RgbImage::from_pixel(4, 2, image::Rgb([255, 0, 0]));
+        let ply_data = back_project(&depth, &options(), Some(&color)).unwrap();
+        assert_eq!(ply_data.point_vertices[0].color, [1.0, 0.0, 0.0]);
+
+        let color = image::RgbImage::new(4, 1);
+        match back_project(&depth, &options(), Some(&color)) {
+            Err(ImportErrorKind::Payload { row, .. }) => assert_eq!(row, 1),
+            _ => panic!("a smaller color image was accepted"),
+        }
+    }
+
+    #[test]
+    fn float_tiff_is_in_meters() {
+        let mut tiff = Cursor::new(Vec::new());
+        TiffEncoder::new(&mut tiff)
+            .unwrap()
+            .write_image::<colortype::Gray32Float>(2, 1, &[0.5, 1.5])
+            .unwrap();
+        tiff.set_position(0);
+        assert!(is_depth_image(&mut tiff, true).unwrap());
+        let ply_data = import_depth(tiff, true, &options()).unwrap();
+        assert_eq!(ply_data.point_vertices[0].position[2], 0.5);
+        assert_eq!(ply_data.point_vertices[1].position[2], 1.5);
+    }
+
+    #[test]
+    fn eight_bit_images_are_not_depth() {
+        let gray = image::GrayImage::from_pixel(2, 2, image::Luma([100]));
+        assert!(!is_depth_image(png(DynamicImage::ImageLuma8(gray)), false).unwrap());
+
+        let mut tiff = Cursor::new(Vec::new());
+        TiffEncoder::new(&mut tiff)
+            .unwrap()
+            .write_image::<colortype::Gray8>(2, 1, &[1, 2])
+            .unwrap();
+        tiff.set_position(0);
+        assert!(!is_depth_image(&mut tiff, true).unwrap());
+        // the reader is rewound for the import as an image
+        assert_eq!(tiff.position(), 0);
+    }
+}
@@ -0,0 +1,213 @@
+//! Reader for ASTM E57 files, which can hold several scans of a terrestrial laser scanner.
+
+use super::{Cloud, ImportErrorKind, PlyData, ScalarField};
+use crate::points::Vertex;
+use e57::{CartesianCoordinate, E57Reader};
+use std::io::{Read, Seek};
+
+pub fn import_e57(reader: impl Read + Seek) -> Result<PlyData, ImportErrorKind> {
+    let mut reader = E57Reader::new(reader)?;
+
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut intensity = Vec::new();
+    let mut clouds = Vec::new();
+    for (i, pointcloud) in reader.pointclouds().iter().enumerate() {
+        // The reader applies the pose of the scan and converts spherical coordinates, so all
+        // points end up in the same coordinate system.
+        let start = positions.len();
+        for point in reader.pointcloud_simple(pointcloud)? {
+            let point = point?;
+            let (x, y, z) = match point.cartesian {
+                CartesianCoordinate::Valid { x, y, z } => (x, y, z),
+                // directions without a range can't be shown
+                _ => continue,
+            };
+            positions.push([x, y, z]);
+            colors.push(
+                point
+                    .color
+                    .map_or([1.0, 1.0, 1.0], |c| [c.red, c.green, c.blue]),
+            );
+            intensity.push(point.intensity.unwrap_or(f32::NAN));
+        }
+        clouds.push(Cloud {
+            name: pointcloud
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("scan {}", i + 1)),
+            points: start..positions.len(),
+        });
+    }
+
+    // Like lidar data, scans are often georeferenced, so the points are stored relative to the
+    // center of all of them.
+    let origin = match positions.first() {
+        Some(&first) => {
+            let (min, max) = positions.iter().fold((first, first), |(min, max), p| {
+                (
+                    [0, 1, 2].map(|i| min[i].min(p[i])),
+                    [0, 1, 2].map(|i| max[i].max(p[i])),
+                )
+            });
+            [0, 1, 2].map(|i| ((min[i] + max[i]) / 2.0).round())
+        }
+        None => [0.0; 3],
+    };
+    let point_vertices = positions
+        .iter()
+        .zip(colors)
+        .map(|(position, color)| Vertex {
+            position: [0, 1, 2].map(|i| (position[i] - origin[i]) as f32),
+            _padding0: [0; 4],
+            color,
+            _padding1: [0; 4],
+        })
+        .collect();
+
+    let mut scalar_fields = Vec::new();
+    if intensity.iter().any(|value| !value.is_nan()) {
+        scalar_fields.push(ScalarField {
+            name: "intensity".into(),
+            point_values: intensity,
+            face_values: Vec::new(),
+        });
+    }
+    Ok(PlyData {
+        point_vertices,
+        scalar_fields,
+        clouds,
+        origin,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use e57::{E57Writer, Quaternion, Record, RecordValue, Transform, Translation};
+    use std::{f64::consts::FRAC_1_SQRT_2, fs::File};
+
+    fn assert_near(actual: [f32; 3], expected: [f32; 3]) {
+        let near = actual
+            .iter()
+            .zip(expected)
+            .all(|(a, b)| (a - b).abs() < 1e-4);
+        assert!(near, "{:?} != {:?}", actual, expected);
+    }
+
+    /// Writes a scan with a pose and Cartesian coordinates and one with spherical coordinates.
+    fn write_scans(path: &std::path::Path) {
+        let mut writer = E57Writer::from_file(path, "scans").unwrap();
+        let mut scan = writer
+            .add_pointcloud(
+                "north",
+                vec![
+                    Record::CARTESIAN_X_F64,
+                    Record::CARTESIAN_Y_F64,
+                    Record::CARTESIAN_Z_F64,
+                    Record::COLOR_RED_U8,
+                    Record::COLOR_GREEN_U8,
+                    Record::COLOR_BLUE_U8,
+                    Record::INTENSITY_UNIT_F32,
+                ],
+            )
+            .unwrap();
+        scan.set_name(Some("north".into()));
+        // turned by 90° around z and placed far from the origin
+        scan.set_transform(Some(Transform {
+            rotation: Quaternion {
+                w: FRAC_1_SQRT_2,
+                x: 0.0,
+                y: 0.0,
+                z: FRAC_1_SQRT_2,
+            },
+            translation: Translation {
+                x: 1000.0,
+                y: 2000.0,
+                z: 0.0,
+            },
+        }));
+        for (position, color, intensity) in [
+            ([1.0, 0.0, 0.0], [255, 0, 0], 0.5),
+            ([0.0, 0.0, 2.0], [0, 255, 0], 0.25),
+        ] {
+            let mut values = position.map(RecordValue::Double).to_vec();
+            values.extend(color.map(RecordValue::Integer));
+            values.push(RecordValue::Single(intensity));
+            scan.add_point(values).unwrap();
+        }
+        scan.finalize().unwrap();
+
+        let mut scan = writer
+            .add_pointcloud(
+                "unnamed",
+                vec![
+                    Record::SPHERICAL_RANGE_F64,
+                    Record::SPHERICAL_AZIMUTH_F64,
+                    Record::SPHERICAL_ELEVATION_F64,
+                    Record::COLOR_RED_U8,
+                    Record::COLOR_GREEN_U8,
+                    Record::COLOR_BLUE_U8,
+                    Record::INTENSITY_UNIT_F32,
+                ],
+            )
+            .unwrap();
+        for (spherical, intensity) in [
+            ([2.0, 0.0, 0.0], 1.0),
+            ([1.0, std::f64::consts::FRAC_PI_2, 0.0], 0.0),
+        ] {
+            let mut values = spherical.map(RecordValue::Double).to_vec();
+            values.extend([0, 0, 255].map(RecordValue::Integer));
+            values.push(RecordValue::Single(intensity));
+            scan.add_point(values).unwrap();
+        }
+        scan.finalize().unwrap();
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn scans_in_one_coordinate_system() {
+        let dir = std::env::temp_dir().join(format!("ennona-e57-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scans.e57");
+        write_scans(&path);
+        let ply_data = import_e57(File::open(&path).unwrap()).unwrap();
+
+        // the center of all points, rounded
+        assert_eq!(ply_data.origin, [500.0, 1001.0, 1.0]);
+        let positions = ply_data
+            .point_vertices
+            .iter()
+            .map(|vertex| vertex.position)
+            .collect::<Vec<_>>();
+        assert_eq!(positions.len(), 4);
+        // the pose of the first scan is applied
+        assert_near(positions[0], [500.0, 1000.0, -1.0]);
+        assert_near(positions[1], [500.0, 999.0, 1.0]);
+        // the second scan is converted from spherical coordinates
+        assert_near(positions[2], [-498.0, -1001.0, -1.0]);
+        assert_near(positions[3], [-500.0, -1000.0, -1.0]);
+
+        let colors = ply_data.point_vertices.iter().map(|vertex| vertex.color);
+        assert!(colors.eq([
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0]
+        ]));
+        assert_eq!(ply_data.scalar_fields[0].name, "intensity");
+        assert_eq!(
+            ply_data.scalar_fields[0].point_values,
+            [0.5, 0.25, 1.0, 0.0]
+        );
+
+        let clouds = ply_data
+            .clouds
+            .iter()
+            .map(|cloud| (cloud.name.as_str(), cloud.points.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(clouds, [("north", 0..2), ("scan 2", 2..4)]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
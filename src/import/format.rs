@@ -0,0 +1,159 @@
+//! Detection of the format of a file from its first bytes, with its extension as a hint.
+
+use super::kitti;
+
+/// The formats that files can be imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ply,
+    Pcd,
+    E57,
+    Las,
+    Obj,
+    KittiBin,
+    Bundler,
+    Nvm,
+    Stl,
+    Gltf,
+    /// Delimited text with a point per line.
+    Text,
+    Tiff,
+    /// Any other image, such as a PNG or JPEG.
+    Image,
+}
+
+/// Signatures at the very start of a file that identify its format.
+const SIGNATURES: [(&[u8], Format); 12] = [
+    (b"ply\n", Format::Ply),
+    (b"ply\r\n", Format::Ply),
+    (b"# .PCD", Format::Pcd),
+    (b"VERSION", Format::Pcd),
+    (b"LASF", Format::Las),
+    (b"ASTM-E57", Format::E57),
+    (b"glTF", Format::Gltf),
+    (b"# Bundle file", Format::Bundler),
+    (b"NVM_V3", Format::Nvm),
+    (b"\x89PNG\r\n\x1a\n", Format::Image),
+    (b"II*\0", Format::Tiff),
+    (b"MM\0*", Format::Tiff),
+];
+
+const JPEG_SIGNATURE: &[u8] = b"\xff\xd8\xff";
+
+/// Keywords that OBJ lines start with.
+const OBJ_KEYWORDS: [&str; 9] = ["v", "vn", "vt", "f", "o", "g", "s", "mtllib", "usemtl"];
+
+const STL_HEADER_SIZE: usize = 80;
+const STL_FACET_SIZE: u64 = 50;
+
+impl Format {
+    /// Returns the format of files with `extension`, ignoring its case.
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ply" => Some(Format::Ply),
+            "pcd" => Some(Format::Pcd),
+            "e57" => Some(Format::E57),
+            "las" | "laz" => Some(Format::Las),
+            "obj" => Some(Format::Obj),
+            "bin" => Some(Format::KittiBin),
+            "out" => Some(Format::Bundler),
+            "nvm" => Some(Format::Nvm),
+            "stl" => Some(Format::Stl),
+            "gltf" | "glb" => Some(Format::Gltf),
+            "xyz" | "csv" | "txt" => Some(Format::Text),
+            "tif" | "tiff" => Some(Format::Tiff),
+            "png" | "jpg" | "jpeg" => Some(Format::Image),
+            _ => None,
+        }
+    }
+
+    /// Detects the format of a file of `size` bytes that starts with `header`.
+    ///
+    /// Signatures take precedence over the extension, so misnamed files are still read
+    /// correctly. Formats without a signature, such as KITTI scans, are only recognized by their
+    /// extension, and files without a known one are guessed from their content.
+    pub fn detect(header: &[u8], size: Option<u64>, extension: Option<&str>) -> Option<Format> {
+        if let Some(format) = Format::from_signature(header) {
+            return Some(format);
+        }
+        if let Some(format) = extension.and_then(Format::from_extension) {
+            // `.bin` is used by many other formats, KITTI scans at least fit whole records
+            let is_scan = match size {
+                Some(size) => kitti::is_scan_size(size),
+                None => true,
+            };
+            if format != Format::KittiBin || is_scan {
+                return Some(format);
+            }
+        }
+        Format::from_content(header, size)
+    }
+
+    fn from_signature(header: &[u8]) -> Option<Format> {
+        if header.starts_with(JPEG_SIGNATURE) {
+            return Some(Format::Image);
+        }
+        SIGNATURES
+            .iter()
+            .find(|(signature, _)| header.starts_with(signature))
+            .map(|&(_, format)| format)
+    }
+
+    fn from_content(header: &[u8], size: Option<u64>) -> Option<Format> {
+        // binary STL files have an arbitrary header, but their size follows from the facet count
+        let facet_count = header
+            .get(STL_HEADER_SIZE..STL_HEADER_SIZE + 4)
+            .map(|count| u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as u64);
+        if let (Some(count), Some(size)) = (facet_count, size) {
+            if size == STL_HEADER_SIZE as u64 + 4 + count * STL_FACET_SIZE {
+                return Some(Format::Stl);
+            }
+        }
+        // either an ASCII file or a binary one that is cut off
+        let text = header.iter().skip_while(|b| b.is_ascii_whitespace());
+        if text.take(5).eq(b"solid") {
+            return Some(Format::Stl);
+        }
+
+        // the header may end in the middle of a character
+        let text = match std::str::from_utf8(header) {
+            Ok(text) => text,
+            Err(e) if e.error_len().is_none() => {
+                std::str::from_utf8(&header[..e.valid_up_to()]).unwrap_or_default()
+            }
+            Err(_) => return None,
+        };
+        if text.trim_start().starts_with('{') && text.contains("\"asset\"") {
+            return Some(Format::Gltf);
+        }
+        let mut lines = text
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .filter(|first| !first.starts_with('#'));
+        match lines.next() {
+            Some(first) if OBJ_KEYWORDS.contains(&first) => Some(Format::Obj),
+            Some(_) => Some(Format::Text),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bin_files_need_whole_scan_records() {
+        let header = [0xff, 0xfe, 0xfd, 0xfc];
+        assert_eq!(
+            Format::detect(&header, Some(64), Some("bin")),
+            Some(Format::KittiBin)
+        );
+        // a stream of unknown size is assumed to be a scan
+        assert_eq!(
+            Format::detect(&header, None, Some("bin")),
+            Some(Format::KittiBin)
+        );
+        assert_eq!(Format::detect(&header, Some(70), Some("bin")), None);
+    }
+}
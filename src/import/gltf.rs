@@ -0,0 +1,287 @@
+//! Reader for glTF 2.0 scenes, both as `.gltf` with separate buffers and as binary `.glb`.
+
+use super::{ImportErrorKind, PlyData};
+use crate::points::Vertex;
+use gltf::{buffer::Source, mesh::Mode, Document, Node, Primitive};
+use nalgebra::{Matrix4, Point3};
+use std::{fs, io::Read, path::Path};
+
+/// Imports the default scene of a glTF file, loading external buffers relative to `base_dir`.
+///
+/// The node hierarchy is flattened, so all positions are in scene coordinates.
+pub fn import_gltf(mut reader: impl Read, base_dir: &Path) -> Result<PlyData, ImportErrorKind> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(&bytes)?;
+    let buffers = read_buffers(&document, blob, base_dir)?;
+
+    let mut ply_data = PlyData::default();
+    match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => {
+            for node in scene.nodes() {
+                add_node(&node, &Matrix4::identity(), &buffers, &mut ply_data);
+            }
+        }
+        // without a scene, every node that is not the child of another one is a root
+        None => {
+            let children = document
+                .nodes()
+                .flat_map(|node| node.children().map(|child| child.index()))
+                .collect::<Vec<_>>();
+            for node in document.nodes() {
+                if !children.contains(&node.index()) {
+                    add_node(&node, &Matrix4::identity(), &buffers, &mut ply_data);
+                }
+            }
+        }
+    }
+    Ok(ply_data)
+}
+
+/// Loads the data of all buffers, which are stored in the binary chunk of a `.glb`, in data
+/// URIs or in separate files.
+fn read_buffers(
+    document: &Document,
+    mut blob: Option<Vec<u8>>,
+    base_dir: &Path,
+) -> Result<Vec<Vec<u8>>, ImportErrorKind> {
+    document
+        .buffers()
+        .map(|buffer| {
+            let buffer_error = |message: String| ImportErrorKind::Payload {
+                element: "buffers".into(),
+                row: buffer.index(),
+                message,
+            };
+            let data = match buffer.source() {
+                Source::Bin => blob
+                    .take()
+                    .ok_or_else(|| buffer_error("missing binary chunk".into()))?,
+                Source::Uri(uri) if uri.starts_with("data:") => {
+                    let encoded = uri
+                        .split_once(";base64,")
+                        .map(|(_, encoded)| encoded)
+                        .ok_or_else(|| buffer_error("unsupported data URI".into()))?;
+                    base64::decode(encoded).map_err(|e| buffer_error(e.to_string()))?
+                }
+                Source::Uri(uri) => fs::read(base_dir.join(uri))?,
+            };
+            if data.len() < buffer.length() {
+                return Err(buffer_error(format!(
+                    "{} bytes instead of {}",
+                    data.len(),
+                    buffer.length()
+                )));
+            }
+            Ok(data)
+        })
+        .collect()
+}
+
+/// Adds the meshes of a node and its children, transformed into scene coordinates.
+fn add_node(
+    node: &Node<'_>,
+    parent_transform: &Matrix4<f32>,
+    buffers: &[Vec<u8>],
+    ply_data: &mut PlyData,
+) {
+    let transform = parent_transform * Matrix4::from(node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            add_primitive(&primitive, &transform, buffers, ply_data);
+        }
+    }
+    for child in node.children() {
+        add_node(&child, &transform, buffers, ply_data);
+    }
+}
+
+fn add_primitive(
+    primitive: &Primitive<'_>,
+    transform: &Matrix4<f32>,
+    buffers: &[Vec<u8>],
+    ply_data: &mut PlyData,
+) {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+    let positions = match reader.read_positions() {
+        Some(positions) => positions,
+        None => return,
+    };
+    let mut colors = reader.read_colors(0).map(|colors| colors.into_rgb_f32());
+    let [r, g, b, _] = primitive
+        .material()
+        .pbr_metallic_roughness()
+        .base_color_factor();
+
+    let vertices = positions.map(|position| {
+        let position = transform.transform_point(&Point3::from(position));
+        let color = colors
+            .as_mut()
+            .and_then(Iterator::next)
+            .unwrap_or([1.0, 1.0, 1.0]);
+        Vertex {
+            position: position.coords.into(),
+            _padding0: [0; 4],
+            color: [color[0] * r, color[1] * g, color[2] * b],
+            _padding1: [0; 4],
+        }
+    });
+
+    let offset = ply_data.face_vertices.len() as u32;
+    match primitive.mode() {
+        Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan => {
+            ply_data.face_vertices.extend(vertices);
+            let count = ply_data.face_vertices.len() as u32 - offset;
+            let mut indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..count).collect::<Vec<_>>(),
+            };
+            let triangles = match primitive.mode() {
+                Mode::TriangleStrip => indices
+                    .windows(3)
+                    .enumerate()
+                    // every other triangle of a strip is flipped to keep the winding order
+                    .flat_map(|(i, t)| match i % 2 {
+                        0 => [t[0], t[1], t[2]],
+                        _ => [t[1], t[0], t[2]],
+                    })
+                    .collect(),
+                // an empty fan has no center vertex
+                Mode::TriangleFan => indices
+                    .get(1..)
+                    .unwrap_or_default()
+                    .windows(2)
+                    .flat_map(|t| [indices[0], t[0], t[1]])
+                    .collect(),
+                // an incomplete triangle at the end is dropped
+                _ => {
+                    indices.truncate(indices.len() / 3 * 3);
+                    indices
+                }
+            };
+            if triangles.iter().any(|&i| i >= count) {
+                log::warn!("skipping glTF primitive with out of range indices");
+                ply_data.face_vertices.truncate(offset as usize);
+                return;
+            }
+            ply_data
+                .face_indices
+                .extend(triangles.into_iter().map(|i| i + offset));
+        }
+        // points and lines are shown as points
+        _ => ply_data.point_vertices.extend(vertices),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Four corners of a unit square in the xy plane.
+    fn square() -> Vec<u8> {
+        [
+            0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0,
+        ]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect()
+    }
+
+    /// A scene with one mesh of `primitives` that use the square as positions, stored in
+    /// `buffer`.
+    fn scene(primitives: &str, buffer: &str) -> String {
+        format!(
+            r#"{{
+"asset": {{"version": "2.0"}},
+"scene": 0,
+"scenes": [{{"nodes": [0]}}],
+"nodes": [{{"translation": [1, 0, 0], "children": [1]}}, {{"scale": [2, 2, 2], "mesh": 0}}],
+"meshes": [{{"primitives": [{}]}}],
+"materials": [{{"pbrMetallicRoughness": {{"baseColorFactor": [1, 0, 0, 1]}}}}],
+"buffers": [{}],
+"bufferViews": [{{"buffer": 0, "byteLength": 48}}],
+"accessors": [{{"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+    "min": [0, 0, 0], "max": [1, 1, 0]}}]
+}}"#,
+            primitives, buffer
+        )
+    }
+
+    fn data_uri_buffer() -> String {
+        format!(
+            r#"{{"byteLength": 48, "uri": "data:application/octet-stream;base64,{}"}}"#,
+            base64::encode(square())
+        )
+    }
+
+    #[test]
+    fn nodes_and_materials() {
+        let primitives = r#"{"attributes": {"POSITION": 0}, "mode": 4, "material": 0},
+            {"attributes": {"POSITION": 0}, "mode": 0}"#;
+        let gltf = scene(primitives, &data_uri_buffer());
+        let ply_data = import_gltf(gltf.as_bytes(), Path::new("")).unwrap();
+
+        // only the first three corners make up a whole triangle
+        assert_eq!(ply_data.face_indices, [0, 1, 2]);
+        // scaled by the child node, then translated by its parent
+        assert_eq!(ply_data.face_vertices[2].position, [3.0, 2.0, 0.0]);
+        assert_eq!(ply_data.face_vertices[0].color, [1.0, 0.0, 0.0]);
+        // points are white without a material
+        assert_eq!(ply_data.point_vertices.len(), 4);
+        assert_eq!(ply_data.point_vertices[3].position, [1.0, 2.0, 0.0]);
+        assert_eq!(ply_data.point_vertices[3].color, [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn strips_and_fans() {
+        let primitives = r#"{"attributes": {"POSITION": 0}, "mode": 5},
+            {"attributes": {"POSITION": 0}, "mode": 6}"#;
+        let gltf = scene(primitives, &data_uri_buffer());
+        let ply_data = import_gltf(gltf.as_bytes(), Path::new("")).unwrap();
+        // the second triangle of the strip is flipped, the fan turns around its first corner
+        assert_eq!(ply_data.face_indices, [0, 1, 2, 2, 1, 3, 4, 5, 6, 4, 6, 7]);
+    }
+
+    #[test]
+    fn binary_glb() {
+        let mut json = scene(
+            r#"{"attributes": {"POSITION": 0}}"#,
+            r#"{"byteLength": 48}"#,
+        )
+        .into_bytes();
+        // chunks are aligned to four bytes
+        let padding = (4 - json.len() % 4) % 4;
+        json.resize(json.len() + padding, b' ');
+        let bin = square();
+
+        let mut glb = b"glTF".to_vec();
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((12 + 8 + json.len() as u32 + 8 + bin.len() as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(b"JSON");
+        glb.extend(&json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(b"BIN\0");
+        glb.extend(&bin);
+
+        let ply_data = import_gltf(&glb[..], Path::new("")).unwrap();
+        assert_eq!(ply_data.face_indices, [0, 1, 2]);
+        assert_eq!(ply_data.face_vertices[1].position, [3.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn short_buffer() {
+        let buffer = format!(
+            r#"{{"byteLength": 96, "uri": "data:application/octet-stream;base64,{}"}}"#,
+            base64::encode(square())
+        );
+        let gltf = scene(r#"{"attributes": {"POSITION": 0}}"#, &buffer);
+        assert!(matches!(
+            import_gltf(gltf.as_bytes(), Path::new("")),
+            Err(ImportErrorKind::Payload { row: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+//! Reader for raw lidar scans in the format of the KITTI velodyne data, flat little endian
+//! `f32` records of `x y z reflectance`.
+
+use super::{progress::Progress, ImportErrorKind, PlyData, ScalarField};
+use crate::points::{colormap, Vertex};
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+const RECORD_SIZE: usize = 16;
+
+/// Imports a single scan, colored by the reflectance of its points.
+pub fn import_kitti_bin(mut reader: impl Read) -> Result<PlyData, ImportErrorKind> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() % RECORD_SIZE != 0 {
+        return Err(ImportErrorKind::Payload {
+            element: "points".into(),
+            // the last record is incomplete
+            row: bytes.len() / RECORD_SIZE,
+            message: format!(
+                "file size {} is not a multiple of the {} byte record size",
+                bytes.len(),
+                RECORD_SIZE
+            ),
+        });
+    }
+
+    let mut point_vertices = Vec::with_capacity(bytes.len() / RECORD_SIZE);
+    let mut reflectance = Vec::with_capacity(bytes.len() / RECORD_SIZE);
+    for record in bytes.chunks_exact(RECORD_SIZE) {
+        let mut values = [0.0; 4];
+        LittleEndian::read_f32_into(record, &mut values);
+        let [x, y, z, r] = values;
+        point_vertices.push(Vertex {
+            position: [x, y, z],
+            _padding0: [0; 4],
+            // reflectance is between zero and one, coloring all frames the same way
+            color: colormap(r),
+            _padding1: [0; 4],
+        });
+        reflectance.push(r);
+    }
+
+    Ok(PlyData {
+        point_vertices,
+        scalar_fields: vec![ScalarField {
+            name: "reflectance".into(),
+            point_values: reflectance,
+            face_values: Vec::new(),
+        }],
+        ..Default::default()
+    })
+}
+
+/// Returns the scans in a directory, ordered by their frame numbers.
+///
+/// Scans are `.bin` files named by their frame number, like `000042.bin`, with a size that fits
+/// whole records. Other binary files in the directory are not part of the sequence. Progress is
+/// reported by the entries of the directory that were looked at.
+pub fn scan_files(dir: &Path, progress: &Progress) -> Result<Vec<PathBuf>, ImportErrorKind> {
+    let entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    let mut files = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        progress.check_cancelled()?;
+        // like files, large directories report every percent
+        if i % (entries.len() / 100).max(1) == 0 {
+            progress.report(i as f32 / entries.len() as f32);
+        }
+        let path = entry.path();
+        let is_numbered = matches!(
+            path.file_stem().and_then(|stem| stem.to_str()),
+            Some(stem) if !stem.is_empty() && stem.bytes().all(|b| b.is_ascii_digit())
+        );
+        if is_numbered
+            && path.extension() == Some("bin".as_ref())
+            && is_scan_size(entry.metadata()?.len())
+            && path.is_file()
+        {
+            files.push(path);
+        }
+    }
+    // frame numbers may or may not have leading zeros, even within a directory
+    files.sort_by_key(|path| {
+        let stem = path.file_stem().unwrap_or_default();
+        let number = stem.to_str().and_then(|stem| stem.parse::<u64>().ok());
+        // numbers that are too long for an integer come last, in the order of their names
+        (number.is_none(), number, stem.to_owned())
+    });
+    Ok(files)
+}
+
+/// Returns if a file of `size` bytes can hold a scan.
+pub(super) fn is_scan_size(size: u64) -> bool {
+    size % RECORD_SIZE as u64 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(points: &[[f32; 4]]) -> Vec<u8> {
+        points
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn reads_records() {
+        let bytes = scan(&[[1.0, 2.0, 3.0, 0.0], [4.0, 5.0, 6.0, 1.0]]);
+        let ply_data = import_kitti_bin(&bytes[..]).unwrap();
+        assert_eq!(ply_data.point_vertices[1].position, [4.0, 5.0, 6.0]);
+        assert_eq!(ply_data.point_vertices[0].color, colormap(0.0));
+        assert_eq!(ply_data.scalar_fields[0].name, "reflectance");
+        assert_eq!(ply_data.scalar_fields[0].point_values, [0.0, 1.0]);
+    }
+
+    #[test]
+    fn incomplete_record() {
+        let bytes = scan(&[[1.0, 2.0, 3.0, 0.0], [4.0, 5.0, 6.0, 1.0]]);
+        assert!(matches!(
+            import_kitti_bin(&bytes[..20]),
+            Err(ImportErrorKind::Payload { row: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn finds_numbered_scans() {
+        let dir = std::env::temp_dir().join(format!("ennona-kitti-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let record = scan(&[[0.0; 4]]);
+        for name in [
+            "10.bin",
+            "9.bin",
+            "000011.bin",
+            "010.bin",
+            "calib.bin",
+            "12.txt",
+        ] {
+            fs::write(dir.join(name), &record).unwrap();
+        }
+        // not a whole number of records
+        fs::write(dir.join("13.bin"), &record[..12]).unwrap();
+
+        let files = scan_files(&dir, &Progress::default()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let names = files
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap());
+        assert!(names.eq(["9.bin", "010.bin", "10.bin", "000011.bin"]));
+    }
+}
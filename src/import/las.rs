@@ -0,0 +1,152 @@
+//! Reader for ASPRS LAS lidar files, including LAZ compressed ones.
+
+use super::{ImportErrorKind, PlyData, ScalarField, MAX_RESERVED_ROWS};
+use crate::points::Vertex;
+use las::Read as _;
+use std::{
+    fmt::Debug,
+    io::{Read, Seek},
+};
+
+pub fn import_las<R: Read + Seek + Send + Debug>(reader: R) -> Result<PlyData, ImportErrorKind> {
+    let mut reader = las::Reader::new(reader)?;
+    let header = reader.header();
+    let num_points = (header.number_of_points() as usize).min(MAX_RESERVED_ROWS);
+
+    // Lidar coordinates are often georeferenced and far too large for `f32`, so the points are
+    // stored relative to the center of the cloud.
+    let bounds = header.bounds();
+    let origin = [
+        ((bounds.min.x + bounds.max.x) / 2.0).round(),
+        ((bounds.min.y + bounds.max.y) / 2.0).round(),
+        ((bounds.min.z + bounds.max.z) / 2.0).round(),
+    ];
+
+    let mut vertices = Vec::with_capacity(num_points);
+    let mut colors = Vec::with_capacity(num_points);
+    let mut intensity = Vec::with_capacity(num_points);
+    let mut return_number = Vec::with_capacity(num_points);
+    let mut classification = Vec::with_capacity(num_points);
+    for point in reader.points() {
+        // `las` already applies the scale and offset of the header
+        let point = point?;
+        vertices.push(Vertex {
+            position: [
+                (point.x - origin[0]) as f32,
+                (point.y - origin[1]) as f32,
+                (point.z - origin[2]) as f32,
+            ],
+            _padding0: [0; 4],
+            color: [1.0, 1.0, 1.0],
+            _padding1: [0; 4],
+        });
+        if let Some(color) = point.color {
+            colors.push([color.red, color.green, color.blue]);
+        }
+        intensity.push(point.intensity as f32);
+        return_number.push(point.return_number as f32);
+        classification.push(u8::from(point.classification) as f32);
+    }
+
+    // The specification asks for 16 bit colors, but many writers store 8 bit values instead.
+    if colors.len() == vertices.len() {
+        let max = colors.iter().flatten().copied().max().unwrap_or(0);
+        let scale = if max <= u8::MAX as u16 {
+            u8::MAX as f32
+        } else {
+            u16::MAX as f32
+        };
+        for (vertex, color) in vertices.iter_mut().zip(&colors) {
+            vertex.color = color.map(|c| c as f32 / scale);
+        }
+    }
+
+    let field = |name: &str, point_values| ScalarField {
+        name: name.to_owned(),
+        point_values,
+        face_values: Vec::new(),
+    };
+    Ok(PlyData {
+        point_vertices: vertices,
+        scalar_fields: vec![
+            field("intensity", intensity),
+            field("return_number", return_number),
+            field("classification", classification),
+        ],
+        origin,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use las::{point::Classification, Builder, Color, Point, Write as _, Writer};
+    use std::io::Cursor;
+
+    /// Writes a LAS file with the point format `format` and three points along x.
+    fn write_las(format: u8, color: Option<Color>) -> Cursor<Vec<u8>> {
+        let mut builder = Builder::from((1, 2));
+        builder.point_format = las::point::Format::new(format).unwrap();
+        builder.transforms.x.scale = 0.01;
+        builder.transforms.y.scale = 0.01;
+        builder.transforms.z.scale = 0.01;
+        let header = builder.into_header().unwrap();
+        let mut writer = Writer::new(Cursor::new(Vec::new()), header).unwrap();
+        for i in 0..3 {
+            writer
+                .write(Point {
+                    x: 500_000.0 + i as f64,
+                    y: 4_000_000.0,
+                    z: 10.5,
+                    intensity: 100 * i,
+                    return_number: i as u8 + 1,
+                    number_of_returns: 3,
+                    classification: Classification::Ground,
+                    color,
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let mut cursor = writer.into_inner().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    #[test]
+    fn points_relative_to_origin() {
+        let ply_data = import_las(write_las(0, None)).unwrap();
+        assert_eq!(ply_data.origin, [500_001.0, 4_000_000.0, 11.0]);
+        let positions = ply_data.point_vertices.iter().map(|v| v.position);
+        assert!(positions.eq([[-1.0, 0.0, -0.5], [0.0, 0.0, -0.5], [1.0, 0.0, -0.5]]));
+        assert_eq!(ply_data.point_vertices[0].color, [1.0; 3]);
+    }
+
+    #[test]
+    fn attributes() {
+        let ply_data = import_las(write_las(0, None)).unwrap();
+        let fields = ply_data
+            .scalar_fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.point_values.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                ("intensity", vec![0.0, 100.0, 200.0]),
+                ("return_number", vec![1.0, 2.0, 3.0]),
+                ("classification", vec![2.0; 3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn colors_of_either_depth() {
+        // 8 bit values written into the 16 bit fields
+        let ply_data = import_las(write_las(2, Some(Color::new(255, 51, 0)))).unwrap();
+        assert_eq!(ply_data.point_vertices[0].color, [1.0, 0.2, 0.0]);
+
+        let ply_data = import_las(write_las(2, Some(Color::new(65535, 13107, 0)))).unwrap();
+        assert_eq!(ply_data.point_vertices[0].color, [1.0, 0.2, 0.0]);
+    }
+}
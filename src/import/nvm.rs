@@ -0,0 +1,151 @@
+//! Reader for VisualSfM `.nvm` reconstructions.
+
+use super::{
+    bundler::Tokens, CameraView, ImportErrorKind, Intrinsics, PlyData, ScalarField,
+    MAX_RESERVED_ROWS,
+};
+use crate::points::Vertex;
+use nalgebra::{IsometryMatrix3, Point3, Quaternion, UnitQuaternion};
+use std::io::Read;
+
+/// Imports the points and cameras of the first model in an NVM file.
+pub fn import_nvm(mut reader: impl Read) -> Result<PlyData, ImportErrorKind> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let header_end = text.find('\n').unwrap_or(text.len());
+    let header = text[..header_end].split_whitespace().collect::<Vec<_>>();
+    // the header may name a calibration shared by all cameras, `FixedK fx cx fy cy`
+    let principal_point = match header[..] {
+        ["NVM_V3"] => [0.0, 0.0],
+        ["NVM_V3", "FixedK", _, cx, _, cy] => {
+            let parse = |value: &str| {
+                value.parse().map_err(|_| {
+                    ImportErrorKind::Header(format!("invalid FixedK value '{}'", value))
+                })
+            };
+            [parse(cx)?, parse(cy)?]
+        }
+        _ => return Err(ImportErrorKind::Header("missing 'NVM_V3' line".into())),
+    };
+    let mut tokens = Tokens::new(text, header_end);
+
+    let camera_count: usize = tokens.next("cameras")?;
+    let mut views = Vec::with_capacity(camera_count.min(MAX_RESERVED_ROWS));
+    for row in 0..camera_count {
+        tokens.row = row;
+        let name = tokens.next_str("cameras")?.to_owned();
+        let focal_length: f32 = tokens.next("cameras")?;
+        let [w, x, y, z] = tokens.next_array::<f64, 4>("cameras")?;
+        let center = tokens.next_array::<f64, 3>("cameras")?;
+        let [radial_distortion, _] = tokens.next_array::<f32, 2>("cameras")?;
+
+        // NVM stores the rotation from world to camera coordinates and the camera center
+        let rotation = UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z));
+        let translation = -(rotation * Point3::from(center)).coords;
+        views.push(CameraView {
+            name,
+            world_to_camera: IsometryMatrix3::from_parts(
+                translation.into(),
+                rotation.to_rotation_matrix(),
+            )
+            .cast(),
+            intrinsics: Intrinsics {
+                model: "NVM".into(),
+                width: 0,
+                height: 0,
+                fx: focal_length,
+                fy: focal_length,
+                cx: principal_point[0],
+                cy: principal_point[1],
+                distortion: vec![radial_distortion],
+            },
+        });
+    }
+
+    tokens.row = 0;
+    let point_count: usize = tokens.next("points")?;
+    let mut point_vertices = Vec::with_capacity(point_count.min(MAX_RESERVED_ROWS));
+    let mut track_lengths = Vec::with_capacity(point_count.min(MAX_RESERVED_ROWS));
+    for row in 0..point_count {
+        tokens.row = row;
+        let position = tokens.next_array::<f32, 3>("points")?;
+        let color = tokens.next_array::<f32, 3>("points")?;
+        let track_length: usize = tokens.next("points")?;
+        // every measurement is an image index, a feature index and its position
+        tokens.skip(track_length * 4, "points")?;
+        point_vertices.push(Vertex {
+            position,
+            _padding0: [0; 4],
+            color: color.map(|c| c / 255.0),
+            _padding1: [0; 4],
+        });
+        track_lengths.push(track_length as f32);
+    }
+
+    Ok(PlyData {
+        point_vertices,
+        scalar_fields: vec![ScalarField {
+            name: "track_length".into(),
+            point_values: track_lengths,
+            face_values: Vec::new(),
+        }],
+        views,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NVM: &str = "NVM_V3 FixedK 500 320 500 240
+
+1
+image.jpg 400 1 0 0 0 1 2 3 0.01 0
+
+2
+1 2 3 10 20 30 2 0 5 1.0 2.0 0 6 3 4
+4 5 6 255 255 255 0
+
+0
+";
+
+    #[test]
+    fn cameras_and_points() {
+        let ply_data = import_nvm(NVM.as_bytes()).unwrap();
+        let view = &ply_data.views[0];
+        assert_eq!(view.name, "image.jpg");
+        assert_eq!(view.intrinsics.fx, 400.0);
+        assert_eq!((view.intrinsics.cx, view.intrinsics.cy), (320.0, 240.0));
+        assert_eq!(view.intrinsics.distortion, [0.01]);
+        // the camera sits at its center
+        let center = view.world_to_camera * Point3::new(1.0, 2.0, 3.0);
+        assert_eq!(center, Point3::origin());
+
+        assert_eq!(ply_data.point_vertices.len(), 2);
+        assert_eq!(ply_data.point_vertices[0].color[0], 10.0 / 255.0);
+        assert_eq!(ply_data.scalar_fields[0].point_values, [2.0, 0.0]);
+    }
+
+    #[test]
+    fn missing_header() {
+        assert!(matches!(
+            import_nvm("1\n".as_bytes()),
+            Err(ImportErrorKind::Header(_))
+        ));
+        assert!(matches!(
+            import_nvm("NVM_V3 FixedK 500 x 500 240\n0\n0\n".as_bytes()),
+            Err(ImportErrorKind::Header(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_cameras() {
+        match import_nvm("NVM_V3\n2\na 1 1 0 0 0 0 0 0 0 0\n".as_bytes()) {
+            Err(ImportErrorKind::Payload { element, row, .. }) => {
+                assert_eq!((element.as_str(), row), ("cameras", 1));
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Reader for Wavefront OBJ meshes and the diffuse colors of their MTL materials.
+
+use super::{ImportErrorKind, PlyData};
+use crate::points::Vertex;
+use itertools::Itertools;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+
+/// Imports an OBJ file, loading material libraries relative to `base_dir`.
+pub fn import_obj(reader: impl BufRead, base_dir: &Path) -> Result<PlyData, ImportErrorKind> {
+    let mut vertices = Vec::new();
+    // whether a vertex has a color of its own, which takes precedence over materials
+    let mut has_color = Vec::new();
+    let mut materials = HashMap::new();
+    let mut material = None;
+    // the corners of each face, and the diffuse color of its material
+    let mut faces: Vec<(Vec<usize>, Option<[f32; 3]>)> = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let mut values = line.split_whitespace();
+        match values.next() {
+            Some("v") => {
+                let values = values
+                    .map(|value| parse_value(value, "vertices", vertices.len()))
+                    .collect::<Result<Vec<f32>, _>>()?;
+                // `v x y z r g b` is a common extension to store vertex colors
+                let color = match values.len() {
+                    6 => Some([values[3], values[4], values[5]]),
+                    3 | 4 => None,
+                    _ => {
+                        return Err(ImportErrorKind::Payload {
+                            element: "vertices".into(),
+                            row: vertices.len(),
+                            message: format!("unexpected number of values: {}", values.len()),
+                        })
+                    }
+                };
+                vertices.push(Vertex {
+                    position: [values[0], values[1], values[2]],
+                    _padding0: [0; 4],
+                    color: color.unwrap_or(WHITE),
+                    _padding1: [0; 4],
+                });
+                has_color.push(color.is_some());
+            }
+            Some("f") => {
+                let face = values
+                    .map(|corner| vertex_index(corner, vertices.len(), faces.len()))
+                    .collect::<Result<Vec<usize>, _>>()?;
+                faces.push((face, material));
+            }
+            Some("mtllib") => {
+                for name in values {
+                    match File::open(base_dir.join(name)) {
+                        Ok(file) => materials.extend(read_materials(BufReader::new(file))?),
+                        // many files refer to libraries that were not copied along with them
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                            log::warn!("material library '{}' not found", name)
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            }
+            Some("usemtl") => {
+                material = values.next().and_then(|name| materials.get(name).copied());
+            }
+            _ => {}
+        }
+    }
+
+    // Corners with different materials need their own face vertex, since colors are stored in
+    // the vertices.
+    let mut face_vertex_lookup = HashMap::new();
+    let mut face_vertices = Vec::new();
+    let mut face_indices = Vec::new();
+    let mut used = vec![false; vertices.len()];
+    for (face, material) in faces {
+        let mut face_iter = face.into_iter().map(|index| {
+            used[index] = true;
+            let color = if has_color[index] { None } else { material };
+            *face_vertex_lookup
+                .entry((index, color.map(|c| c.map(f32::to_bits))))
+                .or_insert_with(|| {
+                    face_vertices.push(Vertex {
+                        color: color.unwrap_or(vertices[index].color),
+                        ..vertices[index]
+                    });
+                    face_vertices.len() as u32 - 1
+                })
+        });
+        // faces are tesselated as triangle fans around their first corner
+        if let Some(first) = face_iter.next() {
+            for (second, third) in face_iter.tuple_windows() {
+                face_indices.extend_from_slice(&[first, second, third]);
+            }
+        }
+    }
+
+    // vertices that are not part of a face are shown as points
+    let point_vertices = vertices
+        .into_iter()
+        .zip(used)
+        .filter(|(_, used)| !used)
+        .map(|(vertex, _)| vertex)
+        .collect();
+
+    Ok(PlyData {
+        point_vertices,
+        face_vertices,
+        face_indices,
+        ..Default::default()
+    })
+}
+
+fn parse_value(value: &str, element: &str, row: usize) -> Result<f32, ImportErrorKind> {
+    value.parse().map_err(|_| ImportErrorKind::Payload {
+        element: element.to_owned(),
+        row,
+        message: format!("invalid value '{}'", value),
+    })
+}
+
+/// Turns a face corner like `3`, `3/1/2` or `-1//2` into the index of its vertex.
+///
+/// Indices start at one, negative indices count back from the last vertex.
+fn vertex_index(corner: &str, vertex_count: usize, row: usize) -> Result<usize, ImportErrorKind> {
+    let index: i64 = corner
+        .split('/')
+        .next()
+        .and_then(|index| index.parse().ok())
+        .ok_or_else(|| ImportErrorKind::Payload {
+            element: "faces".into(),
+            row,
+            message: format!("invalid vertex '{}'", corner),
+        })?;
+    let resolved = if index < 0 {
+        vertex_count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= vertex_count as i64 {
+        return Err(ImportErrorKind::FaceIndexOutOfRange {
+            row,
+            index,
+            vertex_count,
+        });
+    }
+    Ok(resolved as usize)
+}
+
+/// Reads the diffuse color of every material in an MTL file.
+///
+/// Colors that are not given as RGB, such as spectral curves, are ignored.
+fn read_materials(reader: impl BufRead) -> io::Result<HashMap<String, [f32; 3]>> {
+    let mut materials = HashMap::new();
+    let mut name = None;
+    for line in reader.lines() {
+        let line = line?;
+        let mut values = line.split_whitespace();
+        match values.next() {
+            Some("newmtl") => name = values.next().map(str::to_owned),
+            Some("Kd") => {
+                let color = values
+                    .map(|value| value.parse().ok())
+                    .collect::<Option<Vec<f32>>>();
+                if let (Some(name), Some(&[r, g, b])) = (&name, color.as_deref()) {
+                    materials.insert(name.clone(), [r, g, b]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn negative_indices() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 5 5 5\nf -4 -3 -2\nv 0 1 0\nf 1 3 -1\n";
+        let ply_data = import_obj(obj.as_bytes(), Path::new("")).unwrap();
+        assert_eq!(ply_data.face_indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(ply_data.face_vertices[3].position, [0.0, 1.0, 0.0]);
+        // the unused vertex is kept as a point
+        assert_eq!(ply_data.point_vertices.len(), 1);
+        assert_eq!(ply_data.point_vertices[0].position, [5.0, 5.0, 5.0]);
+    }
+
+    #[test]
+    fn corners_with_texture_and_normal_indices() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1/1 2/2/2 3//3 4\n";
+        let ply_data = import_obj(obj.as_bytes(), Path::new("")).unwrap();
+        assert_eq!(ply_data.face_indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn invalid_indices() {
+        let out_of_range = "v 0 0 0\nf 1 2 -2\n";
+        assert!(matches!(
+            import_obj(out_of_range.as_bytes(), Path::new("")),
+            Err(ImportErrorKind::FaceIndexOutOfRange { index: 2, .. })
+        ));
+        let invalid = "v 0 0 0\nf 1 x 1\n";
+        assert!(matches!(
+            import_obj(invalid.as_bytes(), Path::new("")),
+            Err(ImportErrorKind::Payload { row: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn reads_diffuse_colors() {
+        let mtl = "newmtl red\nKd 1 0 0\nnewmtl spectral\nKd spectral curve.rfl\n";
+        let materials = read_materials(mtl.as_bytes()).unwrap();
+        assert_eq!(materials.len(), 1);
+        assert_eq!(materials["red"], [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn material_colors() {
+        let dir = std::env::temp_dir().join(format!("ennona-obj-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("colors.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+
+        // vertex colors take precedence over materials, and faces without one stay white
+        let obj = "mtllib colors.mtl missing.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0 0 0 1
+usemtl red
+f 1 2 3
+usemtl unknown
+f 1 2 3
+";
+        let ply_data = import_obj(obj.as_bytes(), &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let colors = ply_data.face_vertices.iter().map(|v| v.color).collect_vec();
+        assert_eq!(
+            colors,
+            [
+                [1.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0],
+                [1.0, 1.0, 1.0],
+                [1.0, 1.0, 1.0],
+            ]
+        );
+        assert_eq!(ply_data.face_indices, [0, 1, 2, 3, 4, 2]);
+    }
+}
@@ -0,0 +1,601 @@
+//! Reader for Point Cloud Library `.pcd` files in the `ascii`, `binary` and
+//! `binary_compressed` encodings.
+
+use super::{ImportErrorKind, PlyData, ScalarField};
+use crate::points::Vertex;
+use byteorder::{LittleEndian, ReadBytesExt};
+use itertools::Itertools;
+use std::io::{self, BufRead, Read};
+
+/// Name used for the payload in errors, since PCD files have no element names.
+const ELEMENT: &str = "point";
+
+/// How the point data following the header is stored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DataFormat {
+    Ascii,
+    Binary,
+    BinaryCompressed,
+}
+
+/// A single value of a point, fields with a `COUNT` above one are split into several of them.
+#[derive(Debug)]
+struct Component {
+    name: String,
+    /// `I`, `U` or `F` for signed, unsigned and floating point numbers.
+    kind: u8,
+    size: usize,
+}
+
+#[derive(Debug)]
+struct Header {
+    /// Components in the order they are stored in a point.
+    components: Vec<Component>,
+    /// Number of components of each field, used for the column layout of compressed data.
+    field_counts: Vec<usize>,
+    points: usize,
+    data: DataFormat,
+}
+
+pub fn import_pcd(mut reader: impl BufRead) -> Result<PlyData, ImportErrorKind> {
+    let header = read_header(&mut reader)?;
+    let mut cloud = CloudBuilder::new(&header.components);
+
+    match header.data {
+        DataFormat::Ascii => {
+            let mut values = vec![0.0; header.components.len()];
+            let mut lines = reader.lines();
+            for row in 0..header.points {
+                let line = loop {
+                    match lines.next() {
+                        Some(line) => {
+                            let line = line?;
+                            if !line.trim().is_empty() {
+                                break line;
+                            }
+                        }
+                        None => {
+                            return Err(ImportErrorKind::Truncated {
+                                element: ELEMENT.into(),
+                                row,
+                                count: header.points,
+                            })
+                        }
+                    }
+                };
+                let mut tokens = line.split_whitespace();
+                for value in &mut values {
+                    *value = tokens
+                        .next()
+                        .and_then(|token| token.parse().ok())
+                        .ok_or_else(|| ImportErrorKind::Payload {
+                            element: ELEMENT.into(),
+                            row,
+                            message: "invalid or missing value".into(),
+                        })?;
+                }
+                cloud.push(&values);
+            }
+        }
+        DataFormat::Binary => {
+            let record_size = header.components.iter().map(|c| c.size).sum::<usize>();
+            let len = record_size
+                .checked_mul(header.points)
+                .ok_or_else(too_many_points)?;
+            let data = read_payload(&mut reader, len, record_size, header.points)?;
+            let mut offset = 0;
+            let layout = header
+                .components
+                .iter()
+                .map(|component| {
+                    offset += component.size;
+                    (offset - component.size, record_size)
+                })
+                .collect_vec();
+            decode_points(&data, &header, &layout, &mut cloud);
+        }
+        DataFormat::BinaryCompressed => {
+            // Compressed data is stored field by field rather than point by point.
+            let mut layout = Vec::new();
+            let mut field_offset = 0;
+            let mut components = header.components.iter();
+            for &count in &header.field_counts {
+                let field_components = components.by_ref().take(count).collect_vec();
+                let stride = field_components.iter().map(|c| c.size).sum::<usize>();
+                let mut offset = field_offset;
+                for component in field_components {
+                    layout.push((offset, stride));
+                    offset += component.size;
+                }
+                field_offset = stride
+                    .checked_mul(header.points)
+                    .and_then(|size| size.checked_add(field_offset))
+                    .ok_or_else(too_many_points)?;
+            }
+
+            // the points are split into columns, so none of them is complete if the data is cut
+            let truncated = |e: io::Error| payload_error(e, header.points);
+            let compressed_size = reader.read_u32::<LittleEndian>().map_err(truncated)? as usize;
+            let uncompressed_size = reader.read_u32::<LittleEndian>().map_err(truncated)? as usize;
+            let compressed = read_payload(&mut reader, compressed_size, 0, header.points)?;
+            let data = lzf_decompress(&compressed, uncompressed_size).ok_or_else(|| {
+                ImportErrorKind::Payload {
+                    element: ELEMENT.into(),
+                    row: 0,
+                    message: "invalid LZF compressed data".into(),
+                }
+            })?;
+            if data.len() < field_offset {
+                return Err(ImportErrorKind::Truncated {
+                    element: ELEMENT.into(),
+                    row: 0,
+                    count: header.points,
+                });
+            }
+            decode_points(&data, &header, &layout, &mut cloud);
+        }
+    }
+
+    Ok(cloud.finish())
+}
+
+fn read_header(reader: &mut impl BufRead) -> Result<Header, ImportErrorKind> {
+    let mut names = Vec::new();
+    let mut sizes = Vec::new();
+    let mut kinds = Vec::new();
+    let mut counts = Vec::new();
+    let mut width = None;
+    let mut height = 1;
+    let mut points = None;
+
+    let header_error = |message: String| ImportErrorKind::Header(message);
+    let parse_list = |values: &[&str], keyword: &str| -> Result<Vec<usize>, ImportErrorKind> {
+        values
+            .iter()
+            .map(|v| v.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| header_error(format!("invalid {} line", keyword)))
+    };
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(header_error("missing DATA line".into()));
+        }
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let values = tokens.collect_vec();
+        match keyword {
+            "VERSION" | "VIEWPOINT" => {}
+            "FIELDS" => names = values.iter().map(|&v| v.to_owned()).collect(),
+            "SIZE" => sizes = parse_list(&values, keyword)?,
+            "TYPE" => kinds = values.iter().map(|v| v.as_bytes()[0]).collect(),
+            "COUNT" => counts = parse_list(&values, keyword)?,
+            "WIDTH" => width = parse_list(&values, keyword)?.first().copied(),
+            "HEIGHT" => height = parse_list(&values, keyword)?.first().copied().unwrap_or(1),
+            "POINTS" => points = parse_list(&values, keyword)?.first().copied(),
+            "DATA" => {
+                let data = match values.first().copied() {
+                    Some("ascii") => DataFormat::Ascii,
+                    Some("binary") => DataFormat::Binary,
+                    Some("binary_compressed") => DataFormat::BinaryCompressed,
+                    other => {
+                        return Err(header_error(format!(
+                            "unsupported data encoding '{}'",
+                            other.unwrap_or_default()
+                        )))
+                    }
+                };
+
+                // COUNT is optional and defaults to one value per field
+                if counts.is_empty() {
+                    counts = vec![1; names.len()];
+                }
+                if sizes.len() != names.len()
+                    || kinds.len() != names.len()
+                    || counts.len() != names.len()
+                {
+                    return Err(header_error(
+                        "FIELDS, SIZE, TYPE and COUNT have different lengths".into(),
+                    ));
+                }
+
+                let mut components = Vec::new();
+                for (((name, &size), &kind), &count) in
+                    names.iter().zip(&sizes).zip(&kinds).zip(&counts)
+                {
+                    let valid = match kind {
+                        b'I' | b'U' => matches!(size, 1 | 2 | 4 | 8),
+                        b'F' => matches!(size, 4 | 8),
+                        _ => false,
+                    };
+                    if !valid {
+                        return Err(header_error(format!(
+                            "field '{}' has unsupported type {} with size {}",
+                            name, kind as char, size
+                        )));
+                    }
+                    for i in 0..count {
+                        components.push(Component {
+                            name: if count == 1 {
+                                name.clone()
+                            } else {
+                                format!("{}_{}", name, i)
+                            },
+                            kind,
+                            size,
+                        });
+                    }
+                }
+
+                if components.is_empty() {
+                    return Err(header_error("no fields with values".into()));
+                }
+
+                // organized clouds store `WIDTH * HEIGHT` points, which POINTS has to match
+                let points = match (points, width) {
+                    (Some(points), _) => points,
+                    (None, Some(width)) => width.checked_mul(height).ok_or_else(too_many_points)?,
+                    (None, None) => return Err(header_error("missing POINTS line".into())),
+                };
+
+                return Ok(Header {
+                    components,
+                    field_counts: counts,
+                    points,
+                    data,
+                });
+            }
+            other => return Err(header_error(format!("unknown header line '{}'", other))),
+        }
+    }
+}
+
+/// The error for point counts whose data wouldn't even fit in memory.
+fn too_many_points() -> ImportErrorKind {
+    ImportErrorKind::Header("too many points".into())
+}
+
+/// Reads `len` bytes of point data, made of records of `record_size` bytes.
+///
+/// Compressed data has a `record_size` of zero, since no point is complete before its end.
+fn read_payload(
+    reader: &mut impl BufRead,
+    len: usize,
+    record_size: usize,
+    points: usize,
+) -> Result<Vec<u8>, ImportErrorKind> {
+    let mut data = Vec::new();
+    reader.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+        return Err(ImportErrorKind::Truncated {
+            element: ELEMENT.into(),
+            row: data.len().checked_div(record_size).unwrap_or(0),
+            count: points,
+        });
+    }
+    Ok(data)
+}
+
+fn payload_error(e: io::Error, points: usize) -> ImportErrorKind {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => ImportErrorKind::Truncated {
+            element: ELEMENT.into(),
+            row: 0,
+            count: points,
+        },
+        _ => ImportErrorKind::Io(e),
+    }
+}
+
+/// Decodes all points of binary data, where `layout` holds the offset of the first value and
+/// the distance between consecutive values of each component.
+fn decode_points(
+    data: &[u8],
+    header: &Header,
+    layout: &[(usize, usize)],
+    cloud: &mut CloudBuilder,
+) {
+    let mut values = vec![0.0; header.components.len()];
+    for point in 0..header.points {
+        for ((value, component), &(offset, stride)) in
+            values.iter_mut().zip(&header.components).zip(layout)
+        {
+            let bytes = &data[offset + point * stride..];
+            *value = decode_value(bytes, component);
+        }
+        cloud.push(&values);
+    }
+}
+
+fn decode_value(bytes: &[u8], component: &Component) -> f64 {
+    let mut bytes = &bytes[..component.size];
+    // reading from a slice of the right length can't fail
+    match (component.kind, component.size) {
+        (b'I', 1) => bytes[0] as i8 as f64,
+        (b'I', 2) => bytes.read_i16::<LittleEndian>().unwrap() as f64,
+        (b'I', 4) => bytes.read_i32::<LittleEndian>().unwrap() as f64,
+        (b'I', _) => bytes.read_i64::<LittleEndian>().unwrap() as f64,
+        (b'U', 1) => bytes[0] as f64,
+        (b'U', 2) => bytes.read_u16::<LittleEndian>().unwrap() as f64,
+        (b'U', 4) => bytes.read_u32::<LittleEndian>().unwrap() as f64,
+        (b'U', _) => bytes.read_u64::<LittleEndian>().unwrap() as f64,
+        (_, 4) => bytes.read_f32::<LittleEndian>().unwrap() as f64,
+        _ => bytes.read_f64::<LittleEndian>().unwrap(),
+    }
+}
+
+/// Collects decoded points into vertices and scalar fields.
+struct CloudBuilder {
+    position: [Option<usize>; 3],
+    /// Component holding the packed color and if it is stored as a float.
+    color: Option<(usize, bool)>,
+    /// Components stored as scalar fields, along with their names.
+    extra: Vec<(usize, String)>,
+    vertices: Vec<Vertex>,
+    extra_values: Vec<Vec<f32>>,
+}
+
+impl CloudBuilder {
+    fn new(components: &[Component]) -> Self {
+        let mut position = [None; 3];
+        let mut color = None;
+        let mut extra = Vec::new();
+        for (i, component) in components.iter().enumerate() {
+            match component.name.as_str() {
+                "x" => position[0] = Some(i),
+                "y" => position[1] = Some(i),
+                "z" => position[2] = Some(i),
+                "rgb" | "rgba" => color = Some((i, component.kind == b'F')),
+                // padding
+                "_" => {}
+                name => extra.push((i, name.to_owned())),
+            }
+        }
+        Self {
+            position,
+            color,
+            extra_values: vec![Vec::new(); extra.len()],
+            extra,
+            vertices: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, values: &[f64]) {
+        let position = self.position.map(|i| i.map_or(0.0, |i| values[i] as f32));
+        // invalid points, such as missing measurements in organized clouds, are NaN
+        if !position.iter().all(|v| v.is_finite()) {
+            return;
+        }
+
+        // colors are packed into 32 bits as `0xAARRGGBB`, and are often stored as a float
+        let color = match self.color {
+            Some((i, true)) => (values[i] as f32).to_bits(),
+            Some((i, false)) => values[i] as u32,
+            None => 0xffffff,
+        };
+        let channel = |shift: u32| ((color >> shift) & 0xff) as f32 / 255.;
+
+        self.vertices.push(Vertex {
+            position,
+            _padding0: [0; 4],
+            color: [channel(16), channel(8), channel(0)],
+            _padding1: [0; 4],
+        });
+        for ((i, _), column) in self.extra.iter().zip(&mut self.extra_values) {
+            column.push(values[*i] as f32);
+        }
+    }
+
+    fn finish(self) -> PlyData {
+        let scalar_fields = self
+            .extra
+            .into_iter()
+            .zip(self.extra_values)
+            .map(|((_, name), point_values)| ScalarField {
+                name,
+                point_values,
+                face_values: Vec::new(),
+            })
+            .collect();
+        PlyData {
+            point_vertices: self.vertices,
+            scalar_fields,
+            ..Default::default()
+        }
+    }
+}
+
+/// Decompresses LZF data, returning `None` if it is malformed or doesn't have the expected size.
+fn lzf_decompress(input: &[u8], output_len: usize) -> Option<Vec<u8>> {
+    // The size is read from the file, don't reserve more than the input can expand to. The longest
+    // back reference takes 3 bytes and copies 264.
+    let mut output = Vec::with_capacity(output_len.min(input.len().saturating_mul(88)));
+    let mut i = 0;
+    while i < input.len() {
+        let control = input[i] as usize;
+        i += 1;
+        if control < 1 << 5 {
+            // literal run of `control + 1` bytes
+            let literal = input.get(i..i + control + 1)?;
+            output.extend_from_slice(literal);
+            i += control + 1;
+        } else {
+            // back reference, the length is extended by another byte if all 3 bits are set
+            let mut len = control >> 5;
+            if len == 7 {
+                len += *input.get(i)? as usize;
+                i += 1;
+            }
+            len += 2;
+            let distance = ((control & 0x1f) << 8) + *input.get(i)? as usize + 1;
+            i += 1;
+            let start = output.len().checked_sub(distance)?;
+            // the referenced range may overlap the bytes being written
+            for k in start..start + len {
+                output.push(output[k]);
+            }
+        }
+        if output.len() > output_len {
+            return None;
+        }
+    }
+    if output.len() == output_len {
+        Some(output)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(data: &str, points: usize) -> String {
+        format!(
+            "# .PCD v0.7 - Point Cloud Data file format
+VERSION 0.7
+FIELDS x y z rgb intensity normal
+SIZE 4 4 4 4 2 4
+TYPE F F F F U F
+COUNT 1 1 1 1 1 2
+WIDTH {}
+HEIGHT 1
+VIEWPOINT 0 0 0 1 0 0 0
+POINTS {}
+DATA {}
+",
+            points, points, data
+        )
+    }
+
+    /// The packed color `0x00ff8000` stored as a float, as PCL does.
+    fn rgb() -> f32 {
+        f32::from_bits(0x00ff8000)
+    }
+
+    /// The fields of point `i` of the binary test clouds.
+    fn binary_fields(i: usize) -> Vec<Vec<u8>> {
+        vec![
+            (i as f32).to_le_bytes().to_vec(),
+            2f32.to_le_bytes().to_vec(),
+            3f32.to_le_bytes().to_vec(),
+            rgb().to_le_bytes().to_vec(),
+            (i as u16 * 10).to_le_bytes().to_vec(),
+            [0.5f32.to_le_bytes(), 0.25f32.to_le_bytes()].concat(),
+        ]
+    }
+
+    /// Compresses `data` into LZF literal runs.
+    fn lzf_literals(data: &[u8]) -> Vec<u8> {
+        data.chunks(32)
+            .flat_map(|run| std::iter::once(run.len() as u8 - 1).chain(run.iter().copied()))
+            .collect()
+    }
+
+    fn check_binary_cloud(ply_data: &PlyData) {
+        assert_eq!(ply_data.point_vertices.len(), 4);
+        assert_eq!(ply_data.point_vertices[3].position, [3.0, 2.0, 3.0]);
+        assert_eq!(ply_data.point_vertices[0].color, [1.0, 128.0 / 255.0, 0.0]);
+        assert_eq!(
+            ply_data.scalar_fields[0].point_values,
+            [0.0, 10.0, 20.0, 30.0]
+        );
+        assert_eq!(ply_data.scalar_fields[1].point_values, [0.5; 4]);
+        assert_eq!(ply_data.scalar_fields[2].point_values, [0.25; 4]);
+    }
+
+    #[test]
+    fn ascii() {
+        let pcd =
+            header("ascii", 2) + &format!("1 2 3 {:e} 7 0.1 0.2\n\nnan nan nan 0 0 0 0\n", rgb());
+        let ply_data = import_pcd(pcd.as_bytes()).unwrap();
+        // the NaN point is skipped
+        assert_eq!(ply_data.point_vertices.len(), 1);
+        assert_eq!(ply_data.point_vertices[0].position, [1.0, 2.0, 3.0]);
+        assert_eq!(ply_data.point_vertices[0].color, [1.0, 128.0 / 255.0, 0.0]);
+        let names = ply_data.scalar_fields.iter().map(|f| f.name.as_str());
+        assert!(names.eq(["intensity", "normal_0", "normal_1"]));
+    }
+
+    #[test]
+    fn binary() {
+        let mut pcd = header("binary", 4).into_bytes();
+        pcd.extend((0..4).flat_map(binary_fields).flatten());
+        check_binary_cloud(&import_pcd(&pcd[..]).unwrap());
+    }
+
+    #[test]
+    fn binary_compressed() {
+        // compressed data is stored field by field
+        let points = (0..4).map(binary_fields).collect::<Vec<_>>();
+        let columns = (0..6)
+            .flat_map(|field| points.iter().flat_map(move |point| point[field].clone()))
+            .collect::<Vec<u8>>();
+        let compressed = lzf_literals(&columns);
+
+        let mut pcd = header("binary_compressed", 4).into_bytes();
+        pcd.extend((compressed.len() as u32).to_le_bytes());
+        pcd.extend((columns.len() as u32).to_le_bytes());
+        pcd.extend(&compressed);
+        check_binary_cloud(&import_pcd(&pcd[..]).unwrap());
+
+        let truncated = &pcd[..pcd.len() - 5];
+        assert!(matches!(
+            import_pcd(truncated),
+            Err(ImportErrorKind::Truncated {
+                row: 0,
+                count: 4,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn truncated_binary() {
+        let mut pcd = header("binary", 4).into_bytes();
+        pcd.extend((0..3).flat_map(binary_fields).flatten());
+        assert!(matches!(
+            import_pcd(&pcd[..]),
+            Err(ImportErrorKind::Truncated {
+                row: 3,
+                count: 4,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn corrupt_counts() {
+        let too_many = |data: &str| {
+            let pcd = header(data, 4).replace("POINTS 4", "POINTS 18446744073709551615");
+            matches!(import_pcd(pcd.as_bytes()), Err(ImportErrorKind::Header(_)))
+        };
+        assert!(too_many("binary"));
+        assert!(too_many("binary_compressed"));
+
+        let pcd = header("binary", 4).replace("COUNT 1 1 1 1 1 2", "COUNT 0 0 0 0 0 0");
+        assert!(matches!(
+            import_pcd(pcd.as_bytes()),
+            Err(ImportErrorKind::Header(_))
+        ));
+    }
+
+    #[test]
+    fn lzf_back_references() {
+        // "abc" followed by a copy of it
+        let input = [2, b'a', b'b', b'c', 1 << 5, 2];
+        assert_eq!(lzf_decompress(&input, 6).unwrap(), b"abcabc");
+        // a reference that overlaps the bytes it writes
+        let input = [0, b'a', 3 << 5, 0];
+        assert_eq!(lzf_decompress(&input, 6).unwrap(), b"aaaaaa");
+        // a reference before the start of the output
+        assert_eq!(lzf_decompress(&[0, b'a', 1 << 5, 4], 4), None);
+        // a size that doesn't match the header
+        assert_eq!(lzf_decompress(&input, 7), None);
+    }
+}
@@ -0,0 +1,677 @@
+use super::{ImportErrorKind, PlyData, ScalarField};
+use crate::points::Vertex;
+use itertools::Itertools;
+use ply_rs::{
+    parser::Parser,
+    ply::{self, Encoding, ScalarType},
+};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead},
+};
+
+/// Vertex properties that are stored in `Vertex` rather than as a `ScalarField`.
+const VERTEX_PROPERTIES: [&str; 6] = ["x", "y", "z", "red", "green", "blue"];
+
+/// Face properties that hold the vertex indices of the face.
+const FACE_INDEX_PROPERTIES: [&str; 2] = ["vertex_index", "vertex_indices"];
+
+/// Number of vertex records decoded per read in the binary fast path.
+const BINARY_CHUNK_RECORDS: usize = 1 << 16;
+
+#[derive(Debug)]
+pub struct Face {
+    pub vertex_index: Vec<i64>,
+}
+
+/// A vertex as read from a PLY file, along with the values of its extra scalar properties.
+///
+/// This is only used by the generic ply-rs parser, binary vertices without list properties are
+/// decoded in bulk by `read_binary_vertices` instead.
+pub struct PlyVertex {
+    pub vertex: Vertex,
+    pub extra: Vec<f32>,
+}
+
+pub fn import_ply(mut buf_read: impl BufRead) -> Result<PlyData, ImportErrorKind> {
+    let vertex_parser = Parser::<PlyVertex>::new();
+    let face_parser = Parser::<Face>::new();
+    let other_parser = Parser::<ply::DefaultElement>::new();
+    let header = vertex_parser
+        .read_header(&mut buf_read)
+        .map_err(|e| match e.kind() {
+            // ply-rs appends the parser state to the message, only the first line is useful
+            io::ErrorKind::InvalidInput => {
+                let message = e.to_string();
+                ImportErrorKind::Header(message.lines().next().unwrap_or_default().to_owned())
+            }
+            _ => ImportErrorKind::Io(e),
+        })?;
+
+    let mut all_vertices = Vec::new();
+    // one column of values for each extra scalar property
+    let mut extra_values = Vec::new();
+    let mut faces = Vec::new();
+    let mut extra_names = Vec::new();
+
+    // Elements have to be read in header order, even the ones that are discarded.
+    for (_, element) in &header.elements {
+        match element.name.as_str() {
+            "vertex" => {
+                check_vertex_element(element)?;
+                // extra scalar properties are pushed in header order while parsing
+                extra_names = element
+                    .properties
+                    .iter()
+                    .filter(|(name, property)| {
+                        matches!(property.data_type, ply::PropertyType::Scalar(_))
+                            && !VERTEX_PROPERTIES.contains(&name.as_str())
+                    })
+                    .map(|(name, _)| name.clone())
+                    .collect_vec();
+
+                let layout = match header.encoding {
+                    Encoding::Ascii => None,
+                    Encoding::BinaryBigEndian => RecordLayout::new(element, true),
+                    Encoding::BinaryLittleEndian => RecordLayout::new(element, false),
+                };
+                if let Some(layout) = layout {
+                    let (vertices, values) = read_binary_vertices(&mut buf_read, element, &layout)?;
+                    all_vertices = vertices;
+                    extra_values = values;
+                } else {
+                    let ply_vertices =
+                        read_element(&vertex_parser, &mut buf_read, element, header.encoding)?;
+                    all_vertices = ply_vertices.iter().map(|v| v.vertex).collect_vec();
+                    extra_values = (0..extra_names.len())
+                        .map(|field| ply_vertices.iter().map(|v| v.extra[field]).collect_vec())
+                        .collect_vec();
+                }
+            }
+            "face" => {
+                check_face_element(element)?;
+                faces = read_element(&face_parser, &mut buf_read, element, header.encoding)?;
+            }
+            _ => {
+                read_element(&other_parser, &mut buf_read, element, header.encoding)?;
+            }
+        }
+    }
+
+    // make sure every face only refers to existing vertices before building the mesh
+    for (row, face) in faces.iter().enumerate() {
+        for &index in &face.vertex_index {
+            if index < 0 {
+                return Err(ImportErrorKind::NegativeFaceIndex { row, index });
+            }
+            if index as usize >= all_vertices.len() {
+                return Err(ImportErrorKind::FaceIndexOutOfRange {
+                    row,
+                    index,
+                    vertex_count: all_vertices.len(),
+                });
+            }
+        }
+    }
+
+    // maps from ply vertex indices to face vertex indices
+    let mut all_vertices_to_face_vertices = HashMap::new();
+    // ply vertex index of each face vertex
+    let mut face_vertex_sources = Vec::new();
+    let mut face_indices = Vec::new();
+    // looks up face vertex index or creates face vertex for ply vertex
+    let mut get_or_insert_vertex = |index| {
+        *all_vertices_to_face_vertices
+            .entry(index)
+            .or_insert_with(|| {
+                let pos = face_vertex_sources.len() as u32;
+                face_vertex_sources.push(index);
+                pos
+            })
+    };
+    // tesselate faces
+    for face in faces {
+        // turns ply vertices into face vertices
+        let mut face_iter = face
+            .vertex_index
+            .iter()
+            .map(|&i| get_or_insert_vertex(i as usize));
+        // first vertex is center of triangle fan and first vertex
+        // of all triangles
+        if let Some(first) = face_iter.next() {
+            for (second, third) in face_iter.tuple_windows() {
+                // each set of 2 indicies is a new triangle in the triangle fan.
+                face_indices.extend_from_slice(&[first, second, third]);
+            }
+        }
+    }
+
+    // A pure point cloud can be handed over without copying, which matters for huge scans.
+    if face_vertex_sources.is_empty() {
+        let scalar_fields = extra_names
+            .into_iter()
+            .zip(extra_values)
+            .map(|(name, point_values)| ScalarField {
+                name,
+                point_values,
+                face_values: Vec::new(),
+            })
+            .collect_vec();
+
+        return Ok(PlyData {
+            point_vertices: all_vertices,
+            face_vertices: Vec::new(),
+            face_indices,
+            scalar_fields,
+            ..Default::default()
+        });
+    }
+
+    // set of vertices that do not corrispond to faces.
+    let point_vertex_sources = (0..all_vertices.len())
+        .filter(|i| !all_vertices_to_face_vertices.contains_key(i))
+        .collect_vec();
+
+    let vertices_from = |sources: &[usize]| sources.iter().map(|&i| all_vertices[i]).collect_vec();
+    let values_from =
+        |sources: &[usize], values: &[f32]| sources.iter().map(|&i| values[i]).collect_vec();
+
+    let scalar_fields = extra_names
+        .into_iter()
+        .zip(extra_values)
+        .map(|(name, values)| ScalarField {
+            name,
+            point_values: values_from(&point_vertex_sources, &values),
+            face_values: values_from(&face_vertex_sources, &values),
+        })
+        .collect_vec();
+
+    Ok(PlyData {
+        point_vertices: vertices_from(&point_vertex_sources),
+        face_vertices: vertices_from(&face_vertex_sources),
+        face_indices,
+        scalar_fields,
+        ..Default::default()
+    })
+}
+
+/// Reads the rows of an element one at a time with the generic ply-rs parser, so that errors
+/// can name the row they occurred in.
+fn read_element<E: ply::PropertyAccess>(
+    parser: &Parser<E>,
+    reader: &mut impl BufRead,
+    element: &ply::ElementDef,
+    encoding: Encoding,
+) -> Result<Vec<E>, ImportErrorKind> {
+    // Don't trust the header with the full allocation, the file may be truncated.
+    let mut rows = Vec::with_capacity(element.count.min(BINARY_CHUNK_RECORDS));
+    let mut line = String::new();
+    for row in 0..element.count {
+        let result = match encoding {
+            Encoding::Ascii => {
+                line.clear();
+                match reader.read_line(&mut line) {
+                    Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
+                    Ok(_) => parser.read_ascii_element(&line, element),
+                    Err(e) => Err(e),
+                }
+            }
+            Encoding::BinaryBigEndian => parser.read_big_endian_element(reader, element),
+            Encoding::BinaryLittleEndian => parser.read_little_endian_element(reader, element),
+        };
+        rows.push(result.map_err(|e| payload_error(element, row, e))?);
+    }
+    Ok(rows)
+}
+
+/// Converts an error that occurred while reading `row` of `element`.
+fn payload_error(element: &ply::ElementDef, row: usize, e: io::Error) -> ImportErrorKind {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => ImportErrorKind::Truncated {
+            element: element.name.clone(),
+            row,
+            count: element.count,
+        },
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => {
+            // ply-rs appends the offending line to the message, only the first line is useful
+            let message = e.to_string();
+            ImportErrorKind::Payload {
+                element: element.name.clone(),
+                row,
+                message: message.lines().next().unwrap_or_default().to_owned(),
+            }
+        }
+        _ => ImportErrorKind::Io(e),
+    }
+}
+
+/// Checks that the properties stored in `Vertex` are scalars.
+fn check_vertex_element(element: &ply::ElementDef) -> Result<(), ImportErrorKind> {
+    for (name, property) in &element.properties {
+        if VERTEX_PROPERTIES.contains(&name.as_str())
+            && !matches!(property.data_type, ply::PropertyType::Scalar(_))
+        {
+            return Err(ImportErrorKind::TypeMismatch {
+                element: element.name.clone(),
+                property: name.clone(),
+                expected: "a scalar",
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the face vertex indices are stored as lists of integers.
+fn check_face_element(element: &ply::ElementDef) -> Result<(), ImportErrorKind> {
+    use ply::PropertyType::List;
+
+    for (name, property) in &element.properties {
+        if FACE_INDEX_PROPERTIES.contains(&name.as_str())
+            && matches!(
+                property.data_type,
+                ply::PropertyType::Scalar(_)
+                    | List(_, ScalarType::Float)
+                    | List(_, ScalarType::Double)
+            )
+        {
+            return Err(ImportErrorKind::TypeMismatch {
+                element: element.name.clone(),
+                property: name.clone(),
+                expected: "a list of integers",
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Where the value of a binary vertex property ends up.
+enum Target {
+    Position(usize),
+    Color(usize),
+    Extra(usize),
+}
+
+/// A single property within a fixed size binary record.
+struct RecordField {
+    offset: usize,
+    scalar_type: ScalarType,
+    target: Target,
+}
+
+/// The fixed layout of a binary vertex record.
+pub struct RecordLayout {
+    size: usize,
+    fields: Vec<RecordField>,
+    num_extra: usize,
+    big_endian: bool,
+}
+
+impl RecordLayout {
+    /// Computes the record layout of a binary element, or `None` if it contains list properties
+    /// and therefore has no fixed size, or no properties at all.
+    pub fn new(element: &ply::ElementDef, big_endian: bool) -> Option<Self> {
+        let mut size = 0;
+        let mut fields = Vec::new();
+        let mut num_extra = 0;
+        for (name, property) in &element.properties {
+            let scalar_type = match &property.data_type {
+                ply::PropertyType::Scalar(scalar_type) => scalar_type.clone(),
+                ply::PropertyType::List(..) => return None,
+            };
+            let target = match name.as_str() {
+                "x" => Target::Position(0),
+                "y" => Target::Position(1),
+                "z" => Target::Position(2),
+                "red" => Target::Color(0),
+                "green" => Target::Color(1),
+                "blue" => Target::Color(2),
+                _ => {
+                    num_extra += 1;
+                    Target::Extra(num_extra - 1)
+                }
+            };
+            let offset = size;
+            size += scalar_size(&scalar_type);
+            fields.push(RecordField {
+                offset,
+                scalar_type,
+                target,
+            });
+        }
+        if size == 0 {
+            return None;
+        }
+        Some(Self {
+            size,
+            fields,
+            num_extra,
+            big_endian,
+        })
+    }
+}
+
+/// Reads the fixed layout binary vertex records of `element` in large chunks.
+///
+/// Returns the vertices and one column of values for each extra scalar property.
+pub fn read_binary_vertices(
+    reader: &mut impl BufRead,
+    element: &ply::ElementDef,
+    layout: &RecordLayout,
+) -> Result<(Vec<Vertex>, Vec<Vec<f32>>), ImportErrorKind> {
+    let count = element.count;
+    // Don't trust the header with the full allocation, the file may be truncated.
+    let capacity = count.min(BINARY_CHUNK_RECORDS * 64);
+    let mut vertices = Vec::with_capacity(capacity);
+    let mut extra_values = (0..layout.num_extra)
+        .map(|_| Vec::with_capacity(capacity))
+        .collect_vec();
+
+    let mut buffer = vec![0; layout.size * count.min(BINARY_CHUNK_RECORDS)];
+    let mut remaining = count;
+    while remaining != 0 {
+        let records = remaining.min(BINARY_CHUNK_RECORDS);
+        let chunk = &mut buffer[..records * layout.size];
+        // fill the chunk by hand to know which row the file ends in
+        let mut filled = 0;
+        while filled < chunk.len() {
+            let row = count - remaining + filled / layout.size;
+            match reader.read(&mut chunk[filled..]) {
+                Ok(0) => {
+                    return Err(payload_error(
+                        element,
+                        row,
+                        io::ErrorKind::UnexpectedEof.into(),
+                    ))
+                }
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(payload_error(element, row, e)),
+            }
+        }
+
+        for record in chunk.chunks_exact(layout.size) {
+            let mut vertex = Vertex {
+                position: [0.0, 0.0, 0.0],
+                _padding0: [0; 4],
+                color: [0.0, 0.0, 0.0],
+                _padding1: [0; 4],
+            };
+            for field in &layout.fields {
+                let bytes = &record[field.offset..];
+                let value = decode_scalar(bytes, &field.scalar_type, layout.big_endian);
+                match field.target {
+                    Target::Position(i) => vertex.position[i] = value as f32,
+                    Target::Color(i) => {
+                        vertex.color[i] = normalize_color(value, &field.scalar_type)
+                    }
+                    Target::Extra(i) => extra_values[i].push(value as f32),
+                }
+            }
+            vertices.push(vertex);
+        }
+        remaining -= records;
+    }
+
+    Ok((vertices, extra_values))
+}
+
+/// Size of a binary scalar in bytes.
+fn scalar_size(scalar_type: &ScalarType) -> usize {
+    match scalar_type {
+        ScalarType::Char | ScalarType::UChar => 1,
+        ScalarType::Short | ScalarType::UShort => 2,
+        ScalarType::Int | ScalarType::UInt | ScalarType::Float => 4,
+        ScalarType::Double => 8,
+    }
+}
+
+/// Decodes the binary scalar at the start of `bytes`.
+fn decode_scalar(bytes: &[u8], scalar_type: &ScalarType, big_endian: bool) -> f64 {
+    macro_rules! decode {
+        ($ty:ty, $size:literal) => {{
+            let mut raw = [0; $size];
+            raw.copy_from_slice(&bytes[..$size]);
+            if big_endian {
+                <$ty>::from_be_bytes(raw) as f64
+            } else {
+                <$ty>::from_le_bytes(raw) as f64
+            }
+        }};
+    }
+
+    match scalar_type {
+        ScalarType::Char => bytes[0] as i8 as f64,
+        ScalarType::UChar => bytes[0] as f64,
+        ScalarType::Short => decode!(i16, 2),
+        ScalarType::UShort => decode!(u16, 2),
+        ScalarType::Int => decode!(i32, 4),
+        ScalarType::UInt => decode!(u32, 4),
+        ScalarType::Float => decode!(f32, 4),
+        ScalarType::Double => decode!(f64, 8),
+    }
+}
+
+impl ply::PropertyAccess for PlyVertex {
+    fn new() -> Self {
+        PlyVertex {
+            vertex: Vertex {
+                position: [0.0, 0.0, 0.0],
+                _padding0: [0; 4],
+                color: [0.0, 0.0, 0.0],
+                _padding1: [0; 4],
+            },
+            extra: Vec::new(),
+        }
+    }
+
+    fn set_property(&mut self, key: String, property: ply::Property) {
+        let vertex = &mut self.vertex;
+        match (key.as_ref(), scalar_value(&property)) {
+            ("x", Some((v, _))) => vertex.position[0] = v as f32,
+            ("y", Some((v, _))) => vertex.position[1] = v as f32,
+            ("z", Some((v, _))) => vertex.position[2] = v as f32,
+            ("red", Some((v, ty))) => vertex.color[0] = normalize_color(v, &ty),
+            ("green", Some((v, ty))) => vertex.color[1] = normalize_color(v, &ty),
+            ("blue", Some((v, ty))) => vertex.color[2] = normalize_color(v, &ty),
+            (_, Some((v, _))) => self.extra.push(v as f32),
+            // list properties on vertices are not kept, and the ones above are checked
+            // to be scalars before parsing
+            (_, None) => {}
+        }
+    }
+}
+
+/// Converts a scalar PLY property to `f64` along with its type, returning `None` for list
+/// properties.
+fn scalar_value(property: &ply::Property) -> Option<(f64, ScalarType)> {
+    match *property {
+        ply::Property::Char(v) => Some((v as f64, ScalarType::Char)),
+        ply::Property::UChar(v) => Some((v as f64, ScalarType::UChar)),
+        ply::Property::Short(v) => Some((v as f64, ScalarType::Short)),
+        ply::Property::UShort(v) => Some((v as f64, ScalarType::UShort)),
+        ply::Property::Int(v) => Some((v as f64, ScalarType::Int)),
+        ply::Property::UInt(v) => Some((v as f64, ScalarType::UInt)),
+        ply::Property::Float(v) => Some((v as f64, ScalarType::Float)),
+        ply::Property::Double(v) => Some((v, ScalarType::Double)),
+        _ => None,
+    }
+}
+
+/// Converts a PLY color channel to the `0.0..=1.0` range used by the renderers.
+///
+/// Integer channels are divided by the largest value of their type, while floating point
+/// channels are expected to already be normalized. Negative values are clamped to zero.
+fn normalize_color(value: f64, scalar_type: &ScalarType) -> f32 {
+    let max = match scalar_type {
+        ScalarType::Char => i8::MAX as f64,
+        ScalarType::UChar => u8::MAX as f64,
+        ScalarType::Short => i16::MAX as f64,
+        ScalarType::UShort => u16::MAX as f64,
+        ScalarType::Int => i32::MAX as f64,
+        ScalarType::UInt => u32::MAX as f64,
+        ScalarType::Float | ScalarType::Double => 1.0,
+    };
+    (value / max).clamp(0.0, 1.0) as f32
+}
+
+impl ply::PropertyAccess for Face {
+    fn new() -> Self {
+        Face {
+            vertex_index: Vec::new(),
+        }
+    }
+
+    fn set_property(&mut self, key: String, property: ply::Property) {
+        if !FACE_INDEX_PROPERTIES.contains(&key.as_str()) {
+            // other face properties, such as colors or flags, are not used
+            return;
+        }
+        self.vertex_index = match property {
+            ply::Property::ListChar(vec) => vec.into_iter().map(i64::from).collect(),
+            ply::Property::ListUChar(vec) => vec.into_iter().map(i64::from).collect(),
+            ply::Property::ListShort(vec) => vec.into_iter().map(i64::from).collect(),
+            ply::Property::ListUShort(vec) => vec.into_iter().map(i64::from).collect(),
+            ply::Property::ListInt(vec) => vec.into_iter().map(i64::from).collect(),
+            ply::Property::ListUInt(vec) => vec.into_iter().map(i64::from).collect(),
+            // other types are rejected by `check_face_element` before parsing
+            _ => Vec::new(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+end_header
+";
+
+    #[test]
+    fn reports_row_of_invalid_value() {
+        let ply = format!("{}0 0 0\n1 x 0\n2 0 0\n", HEADER);
+        match import_ply(ply.as_bytes()) {
+            Err(ImportErrorKind::Payload { element, row, .. }) => {
+                assert_eq!((element.as_str(), row), ("vertex", 1));
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn reports_row_of_truncation() {
+        let ply = format!("{}0 0 0\n1 0 0\n", HEADER);
+        match import_ply(ply.as_bytes()) {
+            Err(ImportErrorKind::Truncated { row, count, .. }) => assert_eq!((row, count), (2, 3)),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+
+        let binary = HEADER.replace("ascii", "binary_little_endian");
+        let mut ply = binary.into_bytes();
+        ply.extend((0..7).flat_map(|i| (i as f32).to_le_bytes()));
+        match import_ply(&ply[..]) {
+            Err(ImportErrorKind::Truncated { row, count, .. }) => assert_eq!((row, count), (2, 3)),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    /// A binary file whose vertices have every scalar type, with a row count that doesn't divide
+    /// into chunks of the fast path.
+    fn mixed_binary_ply(big_endian: bool, rows: usize) -> Vec<u8> {
+        let format = if big_endian {
+            "binary_big_endian"
+        } else {
+            "binary_little_endian"
+        };
+        let mut ply = format!(
+            "ply
+format {} 1.0
+element vertex {}
+property double x
+property float y
+property short z
+property uchar red
+property ushort green
+property char blue
+property int label
+property uint id
+end_header
+",
+            format, rows
+        )
+        .into_bytes();
+        macro_rules! push {
+            ($value:expr) => {
+                if big_endian {
+                    ply.extend_from_slice(&$value.to_be_bytes())
+                } else {
+                    ply.extend_from_slice(&$value.to_le_bytes())
+                }
+            };
+        }
+        for i in 0..rows {
+            push!(i as f64 * 0.5);
+            push!(-(i as f32));
+            push!((i % 1000) as i16 - 500);
+            push!((i % 256) as u8);
+            push!((i * 7 % 65536) as u16);
+            push!((i % 128) as i8);
+            push!(i as i32 - 1000);
+            push!(i as u32 * 3);
+        }
+        ply
+    }
+
+    #[test]
+    fn binary_fast_path_matches_ply_rs() {
+        let rows = BINARY_CHUNK_RECORDS + 3;
+        for (big_endian, encoding) in [
+            (false, Encoding::BinaryLittleEndian),
+            (true, Encoding::BinaryBigEndian),
+        ] {
+            let ply = mixed_binary_ply(big_endian, rows);
+            let parser = Parser::<PlyVertex>::new();
+            let mut reader = &ply[..];
+            let header = parser.read_header(&mut reader).unwrap();
+            let element = &header.elements["vertex"];
+
+            let layout = RecordLayout::new(element, big_endian).unwrap();
+            let mut payload = reader;
+            let (vertices, extra_values) =
+                read_binary_vertices(&mut payload, element, &layout).unwrap();
+            let expected = read_element(&parser, &mut reader, element, encoding).unwrap();
+
+            assert_eq!(vertices.len(), rows);
+            assert_eq!(expected.len(), rows);
+            for (i, (vertex, expected)) in vertices.iter().zip(&expected).enumerate() {
+                assert_eq!(vertex.position, expected.vertex.position);
+                assert_eq!(vertex.color, expected.vertex.color);
+                let extra = extra_values.iter().map(|values| values[i]).collect_vec();
+                assert_eq!(extra, expected.extra);
+            }
+        }
+    }
+
+    #[test]
+    fn vertices_without_properties() {
+        let mut ply = b"ply
+format binary_little_endian 1.0
+element vertex 2
+element point 1
+property float x
+property float y
+property float z
+end_header
+"
+        .to_vec();
+        ply.extend([1.0f32, 2.0, 3.0].iter().flat_map(|v| v.to_le_bytes()));
+        let ply_data = import_ply(&ply[..]).unwrap();
+        let positions = ply_data.point_vertices.iter().map(|v| v.position);
+        assert!(positions.eq([[0.0; 3]; 2]));
+    }
+}
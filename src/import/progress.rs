@@ -0,0 +1,134 @@
+//! Progress reporting and cancellation of imports that run on another thread.
+
+use super::ImportErrorKind;
+use std::{
+    fmt,
+    io::{self, Read, Seek, SeekFrom},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Receives the progress of an import and tells it when to stop.
+pub struct Progress {
+    report: Box<dyn Fn(f32) + Send + Sync>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Progress {
+    /// Calls `report` with the fraction of the file that was read, whenever it changes by a
+    /// percent. The import stops once `cancelled` is set.
+    pub fn new(
+        report: impl Fn(f32) + Send + Sync + 'static,
+        cancelled: Arc<AtomicBool>,
+    ) -> Progress {
+        Progress {
+            report: Box::new(report),
+            cancelled,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Reports the fraction of the work that is done, for imports that aren't measured by the
+    /// bytes read from a file, like the frames of a dataset.
+    pub fn report(&self, fraction: f32) {
+        (self.report)(fraction);
+    }
+
+    /// Fails once the import is cancelled, for imports that check between their steps.
+    pub fn check_cancelled(&self) -> Result<(), ImportErrorKind> {
+        if self.is_cancelled() {
+            Err(ImportErrorKind::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress::new(|_| {}, Arc::default())
+    }
+}
+
+/// Wraps the reader of a file of `total` bytes to report how far into it the import is.
+///
+/// Reads fail once the import is cancelled, which makes every format stop at its next read.
+#[derive(Debug)]
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    /// Bytes of the files that were read before this one.
+    offset: u64,
+    position: u64,
+    total: u64,
+    reported_percent: u64,
+    progress: &'a Progress,
+}
+
+impl<'a, R> ProgressReader<'a, R> {
+    pub fn new(inner: R, total: u64, progress: &'a Progress) -> Self {
+        ProgressReader::with_offset(inner, 0, total, progress)
+    }
+
+    /// Wraps the reader of one of several files that are read one after another, of `total`
+    /// bytes together, which starts after `offset` bytes of the files before it.
+    pub fn with_offset(inner: R, offset: u64, total: u64, progress: &'a Progress) -> Self {
+        ProgressReader {
+            inner,
+            offset,
+            position: offset,
+            total,
+            reported_percent: (offset * 100).checked_div(total).unwrap_or_default(),
+            progress,
+        }
+    }
+
+    fn advance(&mut self, position: u64) {
+        self.position = position;
+        // files of unknown size report nothing
+        if let Some(percent) = (self.position * 100).checked_div(self.total) {
+            if percent != self.reported_percent {
+                self.reported_percent = percent;
+                (self.progress.report)(self.position as f32 / self.total as f32);
+            }
+        }
+    }
+
+    fn check_cancelled(&self) -> io::Result<()> {
+        if self.progress.is_cancelled() {
+            Err(io::Error::new(io::ErrorKind::Other, "import was cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check_cancelled()?;
+        let count = self.inner.read(buf)?;
+        self.advance(self.position + count as u64);
+        Ok(count)
+    }
+}
+
+impl<R: Seek> Seek for ProgressReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.check_cancelled()?;
+        let position = self.inner.seek(pos)?;
+        self.advance(self.offset + position);
+        Ok(position)
+    }
+}
@@ -0,0 +1,253 @@
+//! Reader for STL meshes in both the ASCII and the binary encoding.
+
+use super::{ImportErrorKind, PlyData};
+use crate::points::Vertex;
+use byteorder::{ByteOrder, LittleEndian};
+use itertools::Itertools;
+use std::{collections::HashMap, io::Read};
+
+const HEADER_SIZE: usize = 80;
+const FACET_SIZE: usize = 50;
+
+pub fn import_stl(mut reader: impl Read) -> Result<PlyData, ImportErrorKind> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    // Binary files may start with `solid` as well, so the size is checked first. Text after the
+    // header is unlikely to be ASCII in a binary file.
+    let facet_count = bytes
+        .get(HEADER_SIZE..HEADER_SIZE + 4)
+        .map(|count| LittleEndian::read_u32(count) as usize);
+    let is_binary = match facet_count {
+        Some(count) if bytes.len() == HEADER_SIZE + 4 + count * FACET_SIZE => true,
+        _ => {
+            let start = &bytes[..bytes.len().min(1024)];
+            let text = start.iter().skip_while(|b| b.is_ascii_whitespace());
+            !(text.take(5).eq(b"solid") && start.is_ascii())
+        }
+    };
+
+    let mut mesh = MeshBuilder::default();
+    if is_binary {
+        read_binary(&bytes, &mut mesh)?;
+    } else {
+        read_ascii(&bytes, &mut mesh)?;
+    }
+    Ok(PlyData {
+        face_vertices: mesh.vertices,
+        face_indices: mesh.indices,
+        ..Default::default()
+    })
+}
+
+fn read_binary(bytes: &[u8], mesh: &mut MeshBuilder) -> Result<(), ImportErrorKind> {
+    let count = match bytes.get(HEADER_SIZE..HEADER_SIZE + 4) {
+        Some(count) => LittleEndian::read_u32(count) as usize,
+        None => {
+            return Err(ImportErrorKind::Header(
+                "binary STL is shorter than its header".into(),
+            ))
+        }
+    };
+    let facets = &bytes[HEADER_SIZE + 4..];
+    if facets.len() < count * FACET_SIZE {
+        return Err(ImportErrorKind::Truncated {
+            element: "facets".into(),
+            row: facets.len() / FACET_SIZE,
+            count,
+        });
+    }
+
+    // Materialise Magics stores a default color in the header and uses the color bits the other
+    // way around than VisCAM and SolidView.
+    let header = &bytes[..HEADER_SIZE];
+    let magics_color = header
+        .windows(6)
+        .position(|window| window == b"COLOR=")
+        .and_then(|i| header.get(i + 6..i + 9))
+        .map(|rgb| [rgb[0], rgb[1], rgb[2]].map(|c| c as f32 / 255.0));
+
+    for facet in facets.chunks_exact(FACET_SIZE).take(count) {
+        // the normal in the first 12 bytes is ignored
+        let corner = |i: usize| {
+            let offset = 12 + i * 12;
+            [0, 4, 8].map(|c| LittleEndian::read_f32(&facet[offset + c..]))
+        };
+        let attribute = LittleEndian::read_u16(&facet[48..]);
+        let channel = |shift: u16| ((attribute >> shift) & 0x1f) as f32 / 31.0;
+        let has_color = attribute & 0x8000 != 0;
+        let color = match magics_color {
+            Some(_) if !has_color => [channel(0), channel(5), channel(10)],
+            Some(default) => default,
+            None if has_color => [channel(10), channel(5), channel(0)],
+            None => [1.0, 1.0, 1.0],
+        };
+        mesh.add_polygon(&[corner(0), corner(1), corner(2)], color);
+    }
+    Ok(())
+}
+
+fn read_ascii(bytes: &[u8], mesh: &mut MeshBuilder) -> Result<(), ImportErrorKind> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut corners = Vec::new();
+    let mut row = 0;
+    for line in text.lines() {
+        let mut values = line.split_whitespace();
+        match values.next() {
+            Some("vertex") => {
+                let position = values
+                    .map(|value| value.parse::<f32>().ok())
+                    .collect::<Option<Vec<_>>>();
+                match position.as_deref() {
+                    Some(&[x, y, z]) => corners.push([x, y, z]),
+                    _ => {
+                        return Err(ImportErrorKind::Payload {
+                            element: "facets".into(),
+                            row,
+                            message: format!("invalid vertex '{}'", line.trim()),
+                        })
+                    }
+                }
+            }
+            Some("endloop") => {
+                mesh.add_polygon(&corners, [1.0, 1.0, 1.0]);
+                corners.clear();
+                row += 1;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Collects triangles, welding corners with the same position and color into one vertex.
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    lookup: HashMap<([u32; 3], [u32; 3]), u32>,
+}
+
+impl MeshBuilder {
+    fn add_polygon(&mut self, corners: &[[f32; 3]], color: [f32; 3]) {
+        let indices = corners
+            .iter()
+            .map(|&position| self.vertex_index(position, color))
+            .collect_vec();
+        // polygons are tesselated as triangle fans around their first corner
+        if let Some((&first, rest)) = indices.split_first() {
+            for (&second, &third) in rest.iter().tuple_windows() {
+                self.indices.extend_from_slice(&[first, second, third]);
+            }
+        }
+    }
+
+    fn vertex_index(&mut self, position: [f32; 3], color: [f32; 3]) -> u32 {
+        let vertices = &mut self.vertices;
+        *self
+            .lookup
+            .entry((position.map(f32::to_bits), color.map(f32::to_bits)))
+            .or_insert_with(|| {
+                vertices.push(Vertex {
+                    position,
+                    _padding0: [0; 4],
+                    color,
+                    _padding1: [0; 4],
+                });
+                vertices.len() as u32 - 1
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+
+    /// A binary STL of the square, with `header` at the start and the attribute bytes of every
+    /// facet set to `attribute`.
+    fn binary_square(header: &[u8], attribute: u16) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(HEADER_SIZE, 0);
+        bytes.extend((SQUARE.len() as u32).to_le_bytes());
+        for facet in SQUARE {
+            bytes.extend([0.0f32, 0.0, 1.0].iter().flat_map(|v| v.to_le_bytes()));
+            bytes.extend(facet.iter().flatten().flat_map(|v| v.to_le_bytes()));
+            bytes.extend(attribute.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn colors(ply_data: &PlyData) -> Vec<[f32; 3]> {
+        ply_data.face_vertices.iter().map(|v| v.color).collect()
+    }
+
+    #[test]
+    fn ascii() {
+        let mut stl = String::from("solid square\n");
+        for facet in SQUARE {
+            stl += "  facet normal 0 0 1\n    outer loop\n";
+            for [x, y, z] in facet {
+                stl += &format!("      vertex {} {} {}\n", x, y, z);
+            }
+            stl += "    endloop\n  endfacet\n";
+        }
+        stl += "endsolid square\n";
+
+        let ply_data = import_stl(stl.as_bytes()).unwrap();
+        // shared corners are welded
+        assert_eq!(ply_data.face_vertices.len(), 4);
+        assert_eq!(ply_data.face_indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(ply_data.face_vertices[3].position, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn invalid_ascii_vertex() {
+        let stl = "solid\nfacet normal 0 0 1\nouter loop\nvertex 0 0\n";
+        assert!(matches!(
+            import_stl(stl.as_bytes()),
+            Err(ImportErrorKind::Payload { row: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn binary() {
+        // binary files often start with `solid` as well
+        let ply_data = import_stl(&binary_square(b"solid square", 0)[..]).unwrap();
+        assert_eq!(ply_data.face_vertices.len(), 4);
+        assert_eq!(ply_data.face_indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(colors(&ply_data), [[1.0; 3]; 4]);
+    }
+
+    #[test]
+    fn binary_colors() {
+        // VisCAM and SolidView store red in the high bits
+        let ply_data = import_stl(&binary_square(b"", 0x8000 | 31 << 10)[..]).unwrap();
+        assert_eq!(colors(&ply_data), [[1.0, 0.0, 0.0]; 4]);
+
+        // Magics stores red in the low bits, and facets without a color use the default
+        let header = b"COLOR=\x00\xff\x00\xff";
+        let ply_data = import_stl(&binary_square(header, 31)[..]).unwrap();
+        assert_eq!(colors(&ply_data), [[1.0, 0.0, 0.0]; 4]);
+        let ply_data = import_stl(&binary_square(header, 0x8000)[..]).unwrap();
+        assert_eq!(colors(&ply_data), [[0.0, 1.0, 0.0]; 4]);
+    }
+
+    #[test]
+    fn truncated_binary() {
+        let mut stl = binary_square(b"", 0);
+        stl.truncate(stl.len() - 10);
+        assert!(matches!(
+            import_stl(&stl[..]),
+            Err(ImportErrorKind::Truncated {
+                row: 1,
+                count: 2,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,438 @@
+//! Reader for datasets in the format of the TUM RGB-D benchmark, which lists its color and depth
+//! images in `rgb.txt` and `depth.txt` and the poses of the camera in `groundtruth.txt`.
+
+use super::{
+    depth::{self, DepthOptions, Pinhole},
+    progress::{Progress, ProgressReader},
+    CameraView, ImportError, ImportErrorKind, PlyData, ScalarField,
+};
+use nalgebra::{IsometryMatrix3, Point3, Quaternion, Translation3, UnitQuaternion, Vector3};
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+const LIST_FILES: [&str; 3] = ["rgb.txt", "depth.txt", "groundtruth.txt"];
+
+/// Largest difference between the timestamps of a color and a depth image of the same frame, in
+/// seconds. This is the default of `associate.py` from the benchmark tools.
+const MAX_DIFFERENCE: f64 = 0.02;
+
+/// Largest gap in the trajectory that a pose is interpolated over, in seconds.
+const MAX_POSE_GAP: f64 = 0.1;
+
+/// Depth values per meter of the benchmark, whose depth images are in units of 0.2 millimeters.
+const TUM_DEPTH_SCALE: f32 = 5000.0;
+
+/// Rough number of frames that are fused when a dataset is opened, so large ones are quick to
+/// show.
+const DEFAULT_FUSED_FRAMES: usize = 30;
+
+/// Returns if `dir` contains the image lists and the trajectory of a TUM RGB-D dataset.
+pub fn is_tum_dataset(dir: &Path) -> bool {
+    LIST_FILES.iter().all(|name| dir.join(name).is_file())
+}
+
+/// A color and a depth image taken at about the same time, with the pose of the camera.
+#[derive(Debug, Clone)]
+pub struct RgbdFrame {
+    pub timestamp: f64,
+    pub rgb: PathBuf,
+    pub depth: PathBuf,
+    pub camera_to_world: IsometryMatrix3<f64>,
+}
+
+/// The frames of a dataset that have both images and a pose.
+#[derive(Debug, Clone)]
+pub struct RgbdDataset {
+    pub frames: Vec<RgbdFrame>,
+    /// Intrinsics and depth scale of the camera that took the images.
+    pub options: DepthOptions,
+}
+
+/// Frames from `start` to `end`, both included, of which every `step`th one is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRange {
+    pub start: usize,
+    pub end: usize,
+    pub step: usize,
+}
+
+impl FrameRange {
+    /// Returns a range over all of `frame_count` frames that fuses a few of them.
+    pub fn spread(frame_count: usize) -> FrameRange {
+        FrameRange {
+            start: 0,
+            end: frame_count.saturating_sub(1),
+            step: (frame_count / DEFAULT_FUSED_FRAMES).max(1),
+        }
+    }
+
+    pub fn indices(&self) -> impl Iterator<Item = usize> {
+        (self.start..=self.end).step_by(self.step.max(1))
+    }
+}
+
+/// Reads the image lists and the trajectory of the dataset in `dir` and pairs them up.
+///
+/// Without depth options, the images are assumed to come from the Kinect of the benchmark, with
+/// the intrinsics the dataset recommends for unknown cameras. The depth scale of the benchmark is
+/// used unless the options set one.
+pub fn import_tum(
+    dir: &Path,
+    depth: Option<&DepthOptions>,
+    progress: &Progress,
+) -> Result<RgbdDataset, ImportErrorKind> {
+    let [rgb, depth_images, trajectory] = read_lists(dir, progress)?;
+    let rgb = read_list(&rgb, "rgb.txt")?;
+    let depth_images = read_list(&depth_images, "depth.txt")?;
+    let trajectory = read_trajectory(&trajectory)?;
+
+    let mut frames = Vec::new();
+    for (timestamp, depth_file) in depth_images {
+        let nearest = nearest(&rgb, timestamp);
+        let rgb_file = match nearest {
+            Some((rgb_timestamp, file)) if (rgb_timestamp - timestamp).abs() <= MAX_DIFFERENCE => {
+                file
+            }
+            _ => continue,
+        };
+        // frames outside of the trajectory can't be placed in the scene
+        let camera_to_world = match interpolate_pose(&trajectory, timestamp) {
+            Some(pose) => pose,
+            None => continue,
+        };
+        frames.push(RgbdFrame {
+            timestamp,
+            rgb: dir.join(rgb_file),
+            depth: dir.join(depth_file),
+            camera_to_world,
+        });
+    }
+    if frames.is_empty() {
+        return Err(ImportErrorKind::Payload {
+            element: "depth.txt".into(),
+            row: 0,
+            message: "no depth image has a matching color image and pose".into(),
+        });
+    }
+
+    let mut options = depth.cloned().unwrap_or(DepthOptions {
+        pinhole: Pinhole {
+            fx: 525.0,
+            fy: 525.0,
+            cx: 319.5,
+            cy: 239.5,
+        },
+        depth_scale: None,
+        color_image: None,
+    });
+    options.depth_scale.get_or_insert(TUM_DEPTH_SCALE);
+    Ok(RgbdDataset { frames, options })
+}
+
+impl RgbdDataset {
+    /// Back-projects the frames in `range` with their poses and puts them into one cloud.
+    ///
+    /// Progress is reported by the frames that are done, and the fusion stops between frames
+    /// when it is cancelled.
+    pub fn fuse(&self, range: &FrameRange, progress: &Progress) -> Result<PlyData, ImportError> {
+        let mut fused = PlyData::default();
+        let mut depths = Vec::new();
+        let frames = range
+            .indices()
+            .filter_map(|i| self.frames.get(i))
+            .collect::<Vec<_>>();
+        for (i, frame) in frames.iter().enumerate() {
+            let import_frame = || -> Result<PlyData, ImportErrorKind> {
+                progress.check_cancelled()?;
+                let depth = depth::read_image_depth(BufReader::new(File::open(&frame.depth)?))?;
+                let color = image::open(&frame.rgb)?.to_rgb8();
+                depth::back_project(&depth, &self.options, Some(&color))
+            };
+            let mut data = import_frame().map_err(|kind| ImportError {
+                path: frame.depth.clone(),
+                kind,
+            })?;
+
+            let pose = frame.camera_to_world;
+            fused
+                .point_vertices
+                .extend(data.point_vertices.iter_mut().map(|vertex| {
+                    let position = pose * Point3::from(vertex.position).cast::<f64>();
+                    vertex.position = position.coords.cast::<f32>().into();
+                    *vertex
+                }));
+            depths.extend(data.scalar_fields.swap_remove(0).point_values);
+            fused.views.extend(data.views.pop().map(|view| CameraView {
+                name: frame.rgb.file_name().map_or_else(
+                    || frame.timestamp.to_string(),
+                    |name| name.to_string_lossy().into_owned(),
+                ),
+                world_to_camera: pose.inverse().cast(),
+                ..view
+            }));
+            progress.report((i + 1) as f32 / frames.len() as f32);
+        }
+        fused.scalar_fields.push(ScalarField {
+            name: "depth".into(),
+            point_values: depths,
+            face_values: Vec::new(),
+        });
+        Ok(fused)
+    }
+}
+
+/// Reads the image lists and the trajectory, reporting progress over the three files together.
+fn read_lists(dir: &Path, progress: &Progress) -> Result<[String; 3], ImportErrorKind> {
+    let paths = LIST_FILES.map(|name| dir.join(name));
+    let total = paths
+        .iter()
+        .map(|path| path.metadata().map(|metadata| metadata.len()))
+        .sum::<io::Result<u64>>()?;
+    let mut offset = 0;
+    let mut lists = [String::new(), String::new(), String::new()];
+    for (path, list) in paths.iter().zip(&mut lists) {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        ProgressReader::with_offset(file, offset, total, progress).read_to_string(list)?;
+        offset += size;
+    }
+    Ok(lists)
+}
+
+/// Reads the timestamps and file names of an image list, ordered by time.
+fn read_list(text: &str, element: &str) -> Result<Vec<(f64, String)>, ImportErrorKind> {
+    let mut entries = Vec::new();
+    for (row, values) in rows(text) {
+        match values[..] {
+            [timestamp, file] => match timestamp.parse::<f64>() {
+                Ok(timestamp) => entries.push((timestamp, file.to_owned())),
+                Err(_) => return Err(invalid_line(element, row, "invalid timestamp")),
+            },
+            _ => {
+                return Err(invalid_line(
+                    element,
+                    row,
+                    "expected a timestamp and a file name",
+                ))
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    Ok(entries)
+}
+
+/// Reads the poses of `timestamp tx ty tz qx qy qz qw` lines, ordered by time.
+fn read_trajectory(text: &str) -> Result<Vec<StampedPose>, ImportErrorKind> {
+    let mut poses = Vec::new();
+    for (row, values) in rows(text) {
+        let values = values
+            .iter()
+            .map(|value| value.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid_line("groundtruth.txt", row, "invalid value"))?;
+        match values[..] {
+            [timestamp, tx, ty, tz, qx, qy, qz, qw] => poses.push(StampedPose {
+                timestamp,
+                translation: Vector3::new(tx, ty, tz),
+                rotation: UnitQuaternion::from_quaternion(Quaternion::new(qw, qx, qy, qz)),
+            }),
+            _ => {
+                return Err(invalid_line(
+                    "groundtruth.txt",
+                    row,
+                    "expected a timestamp, a translation and a rotation",
+                ))
+            }
+        }
+    }
+    poses.sort_by(|a, b| {
+        a.timestamp
+            .partial_cmp(&b.timestamp)
+            .unwrap_or(Ordering::Equal)
+    });
+    Ok(poses)
+}
+
+/// Splits a list into its lines that aren't comments, numbered from zero.
+fn rows(text: &str) -> impl Iterator<Item = (usize, Vec<&str>)> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.split_whitespace().collect::<Vec<_>>()))
+        .filter(|(_, values)| !values.is_empty() && !values[0].starts_with('#'))
+}
+
+fn invalid_line(element: &str, row: usize, message: &str) -> ImportErrorKind {
+    ImportErrorKind::Payload {
+        element: element.to_owned(),
+        row,
+        message: message.to_owned(),
+    }
+}
+
+/// Returns the entry with the timestamp closest to `timestamp` from a list ordered by time.
+fn nearest(entries: &[(f64, String)], timestamp: f64) -> Option<(f64, &str)> {
+    let i = entries.partition_point(|entry| entry.0 < timestamp);
+    [i.checked_sub(1), Some(i)]
+        .iter()
+        .flatten()
+        .filter_map(|&i| entries.get(i))
+        .min_by(|a, b| {
+            let distance = |entry: &&(f64, String)| (entry.0 - timestamp).abs();
+            distance(a)
+                .partial_cmp(&distance(b))
+                .unwrap_or(Ordering::Equal)
+        })
+        .map(|(timestamp, file)| (*timestamp, file.as_str()))
+}
+
+/// A line of the trajectory, the camera to world transformation at a point in time.
+struct StampedPose {
+    timestamp: f64,
+    translation: Vector3<f64>,
+    rotation: UnitQuaternion<f64>,
+}
+
+/// Interpolates the pose of the camera at `timestamp` between the poses before and after it.
+fn interpolate_pose(trajectory: &[StampedPose], timestamp: f64) -> Option<IsometryMatrix3<f64>> {
+    let i = trajectory.partition_point(|pose| pose.timestamp < timestamp);
+    let after = trajectory.get(i)?;
+    let before = match i.checked_sub(1) {
+        Some(before) => &trajectory[before],
+        // only a pose at exactly the same time can be used for the first one
+        None if after.timestamp == timestamp => after,
+        None => return None,
+    };
+    let gap = after.timestamp - before.timestamp;
+    if gap > MAX_POSE_GAP {
+        return None;
+    }
+    let s = if gap > 0.0 {
+        (timestamp - before.timestamp) / gap
+    } else {
+        0.0
+    };
+    Some(IsometryMatrix3::from_parts(
+        Translation3::from(before.translation.lerp(&after.translation, s)),
+        before
+            .rotation
+            .slerp(&after.rotation, s)
+            .to_rotation_matrix(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{f64::consts::FRAC_1_SQRT_2, fs};
+
+    fn pose(timestamp: f64, x: f64) -> StampedPose {
+        StampedPose {
+            timestamp,
+            translation: Vector3::new(x, 0.0, 0.0),
+            rotation: UnitQuaternion::identity(),
+        }
+    }
+
+    #[test]
+    fn nearest_entry() {
+        let entries = [(1.0, "a".to_owned()), (2.0, "b".to_owned())];
+        assert_eq!(nearest(&entries, 0.5), Some((1.0, "a")));
+        assert_eq!(nearest(&entries, 1.4), Some((1.0, "a")));
+        assert_eq!(nearest(&entries, 1.6), Some((2.0, "b")));
+        assert_eq!(nearest(&entries, 3.0), Some((2.0, "b")));
+        assert_eq!(nearest(&[], 1.0), None);
+    }
+
+    #[test]
+    fn interpolated_poses() {
+        let trajectory = [pose(1.0, 0.0), pose(1.08, 1.0), pose(2.0, 2.0)];
+        let between = interpolate_pose(&trajectory, 1.02).unwrap();
+        assert!((between.translation.x - 0.25).abs() < 1e-9);
+        assert_eq!(
+            interpolate_pose(&trajectory, 1.0).unwrap().translation.x,
+            0.0
+        );
+        // before the first pose, after the last one and over a gap
+        assert!(interpolate_pose(&trajectory, 0.9).is_none());
+        assert!(interpolate_pose(&trajectory, 2.1).is_none());
+        assert!(interpolate_pose(&trajectory, 1.5).is_none());
+    }
+
+    #[test]
+    fn dataset() {
+        let dir = std::env::temp_dir().join(format!("ennona-tum-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // the left column has no measurement, the others are a meter away
+        let depth = image::ImageBuffer::from_fn(4, 2, |x, _| {
+            image::Luma([if x == 0 { 0u16 } else { 5000 }])
+        });
+        let color = image::RgbImage::from_pixel(4, 2, image::Rgb([0, 255, 0]));
+        for i in 1..=3 {
+            depth.save(dir.join(format!("depth{}.png", i))).unwrap();
+            color.save(dir.join(format!("rgb{}.png", i))).unwrap();
+        }
+        let write = |name: &str, text: &str| fs::write(dir.join(name), text).unwrap();
+        write(
+            "rgb.txt",
+            "# timestamp filename\n1.01 rgb1.png\n2.0 rgb2.png\n3.05 rgb3.png\n",
+        );
+        // the third color image is too late and there is no pose for the third depth image
+        write(
+            "depth.txt",
+            "# timestamp filename\n3.0 depth3.png\n1.0 depth1.png\n2.0 depth2.png\n",
+        );
+        let quarter_turn = format!("0 0 0 0 0 {0} {0}", FRAC_1_SQRT_2);
+        write(
+            "groundtruth.txt",
+            &format!(
+                "# timestamp tx ty tz qx qy qz qw\n0.98 0 0 0 0 0 0 1\n1.02 1 0 0 0 0 0 1\n\
+                 1.99 {0}\n2.01 {0}\n",
+                quarter_turn
+            ),
+        );
+        assert!(is_tum_dataset(&dir));
+
+        let dataset = import_tum(&dir, None, &Progress::default()).unwrap();
+        assert_eq!(dataset.options.depth_scale, Some(TUM_DEPTH_SCALE));
+        assert_eq!(dataset.frames.len(), 2);
+        assert_eq!(dataset.frames[0].rgb, dir.join("rgb1.png"));
+        assert_eq!(dataset.frames[1].depth, dir.join("depth2.png"));
+
+        let fused = dataset
+            .fuse(&FrameRange::spread(2), &Progress::default())
+            .unwrap();
+        assert_eq!(fused.point_vertices.len(), 12);
+        assert_eq!(fused.views[0].name, "rgb1.png");
+        assert_eq!(fused.point_vertices[6].color, [0.0, 1.0, 0.0]);
+        // the first frame is shifted by half a meter, the second one turned about z
+        let [x, _, z] = fused.point_vertices[0].position;
+        assert!((x - ((1.0 - 319.5) / 525.0 + 0.5)).abs() < 1e-5);
+        assert!((z - 1.0).abs() < 1e-6);
+        let [x, _, _] = fused.point_vertices[6].position;
+        assert!((x - 239.5 / 525.0).abs() < 1e-4);
+
+        // an explicit depth scale is kept
+        let options = DepthOptions {
+            depth_scale: Some(1000.0),
+            ..dataset.options
+        };
+        let dataset = import_tum(&dir, Some(&options), &Progress::default()).unwrap();
+        let fused = dataset
+            .fuse(&FrameRange::spread(1), &Progress::default())
+            .unwrap();
+        assert!((fused.point_vertices[0].position[2] - 5.0).abs() < 1e-5);
+
+        write("groundtruth.txt", "1 2 3\n");
+        match import_tum(&dir, None, &Progress::default()) {
+            Err(ImportErrorKind::Payload { element, row, .. }) => {
+                assert_eq!((element.as_str(), row), ("groundtruth.txt", 0))
+            }
+            _ => panic!("an invalid trajectory was accepted"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
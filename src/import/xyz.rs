@@ -0,0 +1,430 @@
+//! Reader for points stored as delimited text, such as `.xyz`, `.csv` or `.txt` dumps.
+
+use super::{ImportErrorKind, PlyData, ScalarField};
+use crate::points::Vertex;
+use std::{fmt, io::BufRead, str::FromStr};
+
+/// How the columns of a text file are interpreted.
+#[derive(Debug, Clone, Default)]
+pub struct TextOptions {
+    /// Column separator, detected from the first line when `None`.
+    pub delimiter: Option<char>,
+    /// Meaning of the columns, taken from the header row or the number of columns when `None`.
+    pub columns: Option<ColumnMapping>,
+    pub color_range: ColorRange,
+}
+
+/// Meaning of a single column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    X,
+    Y,
+    Z,
+    Red,
+    Green,
+    Blue,
+    /// Any other value, imported as a scalar field.
+    Field(String),
+    /// A column that is not imported.
+    Skip,
+}
+
+impl Column {
+    fn from_name(name: &str) -> Column {
+        match name.to_lowercase().as_str() {
+            "x" => Column::X,
+            "y" => Column::Y,
+            "z" => Column::Z,
+            "r" | "red" => Column::Red,
+            "g" | "green" => Column::Green,
+            "b" | "blue" => Column::Blue,
+            "" | "_" | "-" => Column::Skip,
+            _ => Column::Field(name.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Column::X => write!(f, "x"),
+            Column::Y => write!(f, "y"),
+            Column::Z => write!(f, "z"),
+            Column::Red => write!(f, "r"),
+            Column::Green => write!(f, "g"),
+            Column::Blue => write!(f, "b"),
+            Column::Field(name) => write!(f, "{}", name),
+            Column::Skip => write!(f, "_"),
+        }
+    }
+}
+
+/// The meaning of every column of a text file, written as a list like `x,y,z,_,intensity`.
+///
+/// `x`, `y`, `z`, `r`, `g` and `b` are positions and colors, `_` skips a column and any
+/// other name becomes a scalar field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMapping(pub Vec<Column>);
+
+impl ColumnMapping {
+    fn from_names<'a>(names: impl IntoIterator<Item = &'a str>) -> ColumnMapping {
+        ColumnMapping(names.into_iter().map(Column::from_name).collect())
+    }
+
+    /// Positions and colors for `count` columns without a header: `x y z`, followed by `r g b`
+    /// if there are exactly six columns.
+    fn default_for(count: usize) -> ColumnMapping {
+        let mut columns = vec![Column::X, Column::Y, Column::Z];
+        if count == 6 {
+            columns.extend([Column::Red, Column::Green, Column::Blue]);
+        }
+        for i in columns.len()..count {
+            columns.push(Column::Field(format!("column_{}", i + 1)));
+        }
+        ColumnMapping(columns)
+    }
+
+    fn position(&self, column: &Column) -> Option<usize> {
+        self.0.iter().position(|c| c == column)
+    }
+
+    fn has_coordinates(&self) -> bool {
+        [Column::X, Column::Y, Column::Z]
+            .iter()
+            .all(|column| self.position(column).is_some())
+    }
+}
+
+impl FromStr for ColumnMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let delimiter = sniff_delimiter(s);
+        let mapping = ColumnMapping::from_names(split_line(s.trim(), delimiter));
+        if mapping.has_coordinates() {
+            Ok(mapping)
+        } else {
+            Err("the columns have to include x, y and z".into())
+        }
+    }
+}
+
+impl fmt::Display for ColumnMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, column) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", column)?;
+        }
+        Ok(())
+    }
+}
+
+/// Range of the color values in a text file.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorRange {
+    /// `0..=255` if any color value is larger than one, `0..=1` otherwise.
+    #[default]
+    Auto,
+    Unit,
+    Byte,
+}
+
+impl FromStr for ColorRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorRange::Auto),
+            "1" => Ok(ColorRange::Unit),
+            "255" => Ok(ColorRange::Byte),
+            _ => Err(format!(
+                "unknown color range '{}', expected auto, 1 or 255",
+                s
+            )),
+        }
+    }
+}
+
+/// Parses a column separator, where `\t` and `tab` stand for a tab.
+pub fn parse_delimiter(s: &str) -> Result<char, String> {
+    let mut chars = s.chars();
+    match (s, chars.next(), chars.next()) {
+        ("\\t" | "tab", _, _) => Ok('\t'),
+        (_, Some(c), None) => Ok(c),
+        _ => Err(format!(
+            "invalid delimiter '{}', expected a single character",
+            s
+        )),
+    }
+}
+
+/// Splits a line at `delimiter`, or at runs of whitespace if it is `None`.
+fn split_line(line: &str, delimiter: Option<char>) -> Vec<&str> {
+    match delimiter {
+        None | Some(' ') => line.split_whitespace().collect(),
+        Some(delimiter) => line.split(delimiter).map(str::trim).collect(),
+    }
+}
+
+fn sniff_delimiter(line: &str) -> Option<char> {
+    [',', ';', '\t'].iter().copied().find(|&c| line.contains(c))
+}
+
+fn is_comment(line: &str) -> bool {
+    line.starts_with('#') || line.starts_with("//")
+}
+
+pub fn import_xyz(reader: impl BufRead, options: &TextOptions) -> Result<PlyData, ImportErrorKind> {
+    // errors report the line of the file, counted from zero
+    let mut lines = reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()));
+
+    let (first_row, first_line) = match lines.next() {
+        Some((row, line)) => (row, line?),
+        None => return Ok(PlyData::default()),
+    };
+    let delimiter = options.delimiter.or_else(|| sniff_delimiter(&first_line));
+
+    // The first line is a header if it is not made up of numbers. Headers are sometimes
+    // written as a comment, like `//X,Y,Z` or `# x y z`, so a comment that names all
+    // coordinates is a header as well.
+    let header = first_line.trim_start_matches(['#', '/']);
+    let header = split_line(header, delimiter);
+    let has_header = if is_comment(&first_line) {
+        ColumnMapping::from_names(header.iter().copied()).has_coordinates()
+    } else {
+        header.iter().any(|value| value.parse::<f64>().is_err())
+    };
+    let first_data = if has_header || is_comment(&first_line) {
+        None
+    } else {
+        Some((first_row, Ok(first_line.clone())))
+    };
+    let mut lines = first_data.into_iter().chain(lines);
+
+    let mut builder = match &options.columns {
+        Some(columns) => PointBuilder::new(columns.clone()),
+        None if has_header => PointBuilder::new(ColumnMapping::from_names(header)),
+        None => match lines.next() {
+            Some((row, line)) => {
+                let line = line?;
+                let count = split_line(&line, delimiter).len();
+                let mut builder = PointBuilder::new(ColumnMapping::default_for(count));
+                builder.push(row, &split_line(&line, delimiter))?;
+                builder
+            }
+            None => return Ok(PlyData::default()),
+        },
+    };
+    for (row, line) in lines {
+        let line = line?;
+        if !is_comment(&line) {
+            builder.push(row, &split_line(&line, delimiter))?;
+        }
+    }
+    builder.finish(options.color_range)
+}
+
+/// Collects the values of the rows of a text file.
+struct PointBuilder {
+    /// Number of values a row needs to have.
+    value_count: usize,
+    position: [usize; 3],
+    color: Option<[usize; 3]>,
+    /// Column index of every scalar field.
+    field_columns: Vec<usize>,
+    vertices: Vec<Vertex>,
+    colors: Vec<[f32; 3]>,
+    fields: Vec<ScalarField>,
+}
+
+impl PointBuilder {
+    fn new(columns: ColumnMapping) -> PointBuilder {
+        let find = |column| columns.position(&column);
+        let position = [
+            find(Column::X).unwrap_or(0),
+            find(Column::Y).unwrap_or(1),
+            find(Column::Z).unwrap_or(2),
+        ];
+        let color = match (find(Column::Red), find(Column::Green), find(Column::Blue)) {
+            (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+            _ => None,
+        };
+
+        let mut field_columns = Vec::new();
+        let mut fields = Vec::new();
+        for (i, column) in columns.0.iter().enumerate() {
+            let name = match column {
+                Column::Field(name) => name.clone(),
+                // a partial color is kept as separate values
+                Column::Red | Column::Green | Column::Blue if color.is_none() => column.to_string(),
+                _ => continue,
+            };
+            field_columns.push(i);
+            fields.push(ScalarField {
+                name,
+                point_values: Vec::new(),
+                face_values: Vec::new(),
+            });
+        }
+
+        PointBuilder {
+            value_count: columns.0.len().max(3),
+            position,
+            color,
+            field_columns,
+            vertices: Vec::new(),
+            colors: Vec::new(),
+            fields,
+        }
+    }
+
+    /// Adds the values of a point, which are on line `row` of the file.
+    fn push(&mut self, row: usize, values: &[&str]) -> Result<(), ImportErrorKind> {
+        if values.len() < self.value_count {
+            return Err(ImportErrorKind::Payload {
+                element: "points".into(),
+                row,
+                message: format!(
+                    "expected {} values, found {}",
+                    self.value_count,
+                    values.len()
+                ),
+            });
+        }
+        let value = |i: usize| {
+            values[i]
+                .parse::<f32>()
+                .map_err(|_| ImportErrorKind::Payload {
+                    element: "points".into(),
+                    row,
+                    message: format!("invalid value '{}'", values[i]),
+                })
+        };
+
+        let [x, y, z] = self.position;
+        self.vertices.push(Vertex {
+            position: [value(x)?, value(y)?, value(z)?],
+            _padding0: [0; 4],
+            color: [1.0, 1.0, 1.0],
+            _padding1: [0; 4],
+        });
+        if let Some([r, g, b]) = self.color {
+            self.colors.push([value(r)?, value(g)?, value(b)?]);
+        }
+        for (field, &i) in self.fields.iter_mut().zip(&self.field_columns) {
+            field.point_values.push(value(i)?);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, color_range: ColorRange) -> Result<PlyData, ImportErrorKind> {
+        if !self.colors.is_empty() {
+            let is_byte = match color_range {
+                ColorRange::Auto => self.colors.iter().flatten().any(|&c| c > 1.0),
+                ColorRange::Unit => false,
+                ColorRange::Byte => true,
+            };
+            let scale = if is_byte { 1.0 / 255.0 } else { 1.0 };
+            for (vertex, color) in self.vertices.iter_mut().zip(&self.colors) {
+                vertex.color = color.map(|c| (c * scale).clamp(0.0, 1.0));
+            }
+        }
+
+        Ok(PlyData {
+            point_vertices: self.vertices,
+            scalar_fields: self.fields,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(text: &str) -> PlyData {
+        import_xyz(text.as_bytes(), &TextOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn sniffs_delimiter() {
+        assert_eq!(sniff_delimiter("1,2,3"), Some(','));
+        assert_eq!(sniff_delimiter("1;2;3"), Some(';'));
+        assert_eq!(sniff_delimiter("1\t2\t3"), Some('\t'));
+        assert_eq!(sniff_delimiter("1 2  3"), None);
+
+        let ply_data = import("1;2;3\n4; 5 ;6\n");
+        assert_eq!(ply_data.point_vertices[1].position, [4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn columns_from_count() {
+        // six columns are positions and colors
+        let ply_data = import("1 2 3 255 0 128\n4  5\t6 0 255 0\n\n");
+        assert_eq!(ply_data.point_vertices.len(), 2);
+        assert_eq!(ply_data.point_vertices[1].position, [4.0, 5.0, 6.0]);
+        assert_eq!(ply_data.point_vertices[0].color, [1.0, 0.0, 128.0 / 255.0]);
+
+        // any other count adds scalar fields
+        let ply_data = import("# comment\n1 2 3 9\n");
+        assert_eq!(ply_data.scalar_fields[0].name, "column_4");
+        assert_eq!(ply_data.scalar_fields[0].point_values, [9.0]);
+    }
+
+    #[test]
+    fn columns_from_header() {
+        let ply_data = import("x,y,z,intensity\n1,2,3,0.5\n4,5,6,7\n");
+        assert_eq!(ply_data.scalar_fields[0].name, "intensity");
+        assert_eq!(ply_data.scalar_fields[0].point_values, [0.5, 7.0]);
+
+        // a header in a comment, with colors in the unit range
+        let ply_data = import("//X;Y;Z;R;G;B\n1;2;3;1;0.5;0\n");
+        assert_eq!(ply_data.point_vertices[0].color, [1.0, 0.5, 0.0]);
+
+        // a comment that doesn't name the coordinates isn't a header
+        let ply_data = import("# exported points\n1 2 3\n");
+        assert_eq!(ply_data.point_vertices[0].position, [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn columns_from_options() {
+        let options = TextOptions {
+            delimiter: Some('|'),
+            columns: Some("i, x, y, z, _, r, g, b".parse().unwrap()),
+            color_range: ColorRange::Byte,
+        };
+        let ply_data = import_xyz("9|1|2|3|0|1|1|0".as_bytes(), &options).unwrap();
+        assert_eq!(ply_data.point_vertices[0].position, [1.0, 2.0, 3.0]);
+        assert_eq!(
+            ply_data.point_vertices[0].color,
+            [1.0 / 255.0, 1.0 / 255.0, 0.0]
+        );
+        assert_eq!(ply_data.scalar_fields.len(), 1);
+        assert_eq!(ply_data.scalar_fields[0].name, "i");
+
+        assert!("x,y".parse::<ColumnMapping>().is_err());
+        assert_eq!(parse_delimiter("\\t"), Ok('\t'));
+        assert!(parse_delimiter("ab").is_err());
+    }
+
+    #[test]
+    fn invalid_rows() {
+        let options = TextOptions::default();
+        // rows are the lines of the file, including headers, comments and blank lines
+        match import_xyz("x y z\n1 2 3\n\n# scan 2\n4 5\n".as_bytes(), &options) {
+            Err(ImportErrorKind::Payload { row, .. }) => assert_eq!(row, 4),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+        match import_xyz("1 2 3\n\n4 5 6\n7 8 z\n".as_bytes(), &options) {
+            Err(ImportErrorKind::Payload { row, message, .. }) => {
+                assert_eq!((row, message.as_str()), (3, "invalid value 'z'"));
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+}
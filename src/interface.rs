@@ -1,39 +1,162 @@
 use std::time::Duration;
 
-use egui::{Button, CollapsingHeader, Frame, Slider, Stroke, TextureId, Ui};
+use egui::{
+    Button, CollapsingHeader, DragValue, Frame, ProgressBar, Slider, Stroke, TextureId, Ui,
+};
 use winit::{
     event::{KeyboardInput, WindowEvent},
     window::Window,
 };
 
-use crate::{camera::Camera, CameraController};
+use crate::{
+    camera::{Camera, CameraController},
+    export::Encoding,
+    import::{
+        depth::{DepthOptions, Pinhole, DEFAULT_DEPTH_SCALE},
+        tum::FrameRange,
+        xyz::{parse_delimiter, ColorRange},
+        CameraView, Cloud, ImportOptions, ScalarField,
+    },
+};
 
 pub struct ImageTextureId {
+    pub name: String,
     pub texture_id: TextureId,
     pub size: egui::Vec2,
 }
 
+/// Summary of a scalar field of the loaded data, shown in the settings panel.
+pub struct ScalarFieldInfo {
+    pub name: String,
+    pub range: Option<(f32, f32)>,
+}
+
+/// A loaded file in the list of layers.
+pub struct LayerInfo {
+    pub name: String,
+    pub visible: bool,
+}
+
 pub struct Interface {
     // Example stuff:
     pub file_name: String,
+    /// Whether the active layer was read from a file that can be read again.
+    pub can_reload: bool,
     pub window_width: u32,
     pub window_height: u32,
     pub camera_controller: CameraController,
     pub images: Vec<ImageTextureId>,
     pub displayed_image_idx: u32,
     pub camera_scale: f32,
+    pub layers: Vec<LayerInfo>,
+    /// Layer whose settings are shown below the layer list.
+    pub active_layer: usize,
+    /// Layer that should be removed on the next update.
+    pub removed_layer: Option<usize>,
+    pub scalar_fields: Vec<ScalarFieldInfo>,
+    /// Scalar field used to color the points instead of their own colors.
+    pub color_by: Option<usize>,
+    /// Offset of the displayed coordinates from the original ones.
+    pub origin: [f64; 3],
+    /// Error message shown in a popup until the user dismisses it.
+    pub error: Option<String>,
+    pub views: Vec<CameraView>,
+    /// Names of the point clouds of the loaded file, if it has several.
+    pub clouds: Vec<String>,
+    pub visible_clouds: Vec<bool>,
+    /// View the camera should jump to on the next update.
+    pub selected_view: Option<usize>,
+    /// Number of frames of the loaded sequence, zero if it isn't one.
+    pub frame_count: usize,
+    /// Frame of the sequence that should be shown.
+    pub frame: usize,
+    pub playing: bool,
+    /// Number of frames of the loaded RGB-D dataset, zero if it isn't one.
+    pub fusion_frame_count: usize,
+    /// Frames of the RGB-D dataset that are being selected.
+    pub fusion_range: FrameRange,
+    /// Frames of the RGB-D dataset that are fused into the shown cloud.
+    pub fused_range: FrameRange,
+    /// Column mapping for text files, taken from the file when empty.
+    pub text_columns: String,
+    /// Column separator for text files, detected when empty.
+    pub text_delimiter: String,
+    pub color_range: ColorRange,
+    /// Whether images are back-projected as depth maps instead of being shown.
+    pub depth_enabled: bool,
+    pub depth_pinhole: Pinhole,
+    /// Depth values per meter, only used when `depth_scale_set` is, as formats have their own
+    /// defaults otherwise.
+    pub depth_scale: f32,
+    pub depth_scale_set: bool,
+    /// Image that colors back-projected depth maps, not used when empty.
+    pub depth_color: String,
+    /// Set when the current file should be imported again with changed settings.
+    pub reload_requested: bool,
+    /// Fraction of the file that the running import has read, if there is one.
+    pub import_progress: Option<f32>,
+    /// Set when the running import should be stopped.
+    pub cancel_requested: bool,
+    pub export_open: bool,
+    /// File the visible layers are exported to.
+    pub export_path: String,
+    pub export_encoding: Encoding,
+    /// Set when the visible layers should be written to `export_path`.
+    pub export_requested: bool,
+    /// Outcome of the last export, shown below the save button.
+    pub export_status: String,
 }
 
 impl Interface {
     pub fn new(file_name: String, window_width: u32, window_height: u32) -> Self {
         Self {
             file_name,
+            can_reload: false,
             window_width,
             window_height,
             camera_controller: CameraController::new(0.5, 0.000818123),
             images: Vec::new(),
             displayed_image_idx: 0,
             camera_scale: 1.0,
+            layers: Vec::new(),
+            active_layer: 0,
+            removed_layer: None,
+            scalar_fields: Vec::new(),
+            color_by: None,
+            origin: [0.0; 3],
+            error: None,
+            views: Vec::new(),
+            clouds: Vec::new(),
+            visible_clouds: Vec::new(),
+            selected_view: None,
+            frame_count: 0,
+            frame: 0,
+            playing: false,
+            fusion_frame_count: 0,
+            fusion_range: FrameRange::spread(0),
+            fused_range: FrameRange::spread(0),
+            text_columns: String::new(),
+            text_delimiter: String::new(),
+            color_range: ColorRange::Auto,
+            depth_enabled: false,
+            // defaults of the Kinect
+            depth_pinhole: Pinhole {
+                fx: 525.0,
+                fy: 525.0,
+                cx: 319.5,
+                cy: 239.5,
+            },
+            depth_scale: DEFAULT_DEPTH_SCALE,
+            depth_scale_set: false,
+            depth_color: String::new(),
+            reload_requested: false,
+            import_progress: None,
+            cancel_requested: false,
+            export_open: false,
+            export_path: "export.ply".into(),
+            export_encoding: Encoding::Binary,
+            export_requested: false,
+            export_status: String::new(),
         }
     }
 
@@ -42,7 +165,10 @@ impl Interface {
         self.window_height = new_size.height;
     }
 
-    pub fn update_camera(&self, camera: &mut Camera, dt: Duration) {
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+        if let Some(view) = self.selected_view.take().and_then(|i| self.views.get(i)) {
+            camera.view_matrix = view.world_to_camera;
+        }
         self.camera_controller.update_camera(camera, dt);
     }
 
@@ -51,11 +177,157 @@ impl Interface {
         self.camera_controller.speed = scale * 5.0;
     }
 
-    pub fn add_image(&mut self, texture_id: TextureId, size: (f32, f32)) {
-        self.images.push(ImageTextureId {
+    /// Adds a layer to the list and shows its settings.
+    pub fn add_layer(&mut self, name: String) {
+        self.layers.push(LayerInfo {
+            name,
+            visible: true,
+        });
+        self.active_layer = self.layers.len() - 1;
+    }
+
+    /// Removes a layer from the list, moving on to the next one if it was the active layer.
+    pub fn remove_layer(&mut self, index: usize) {
+        self.layers.remove(index);
+        if self.active_layer > index || self.active_layer == self.layers.len() {
+            self.active_layer = self.active_layer.saturating_sub(1);
+        }
+    }
+
+    /// Forgets the settings of the active layer, once there are no layers left.
+    pub fn clear_layer_settings(&mut self) {
+        self.file_name.clear();
+        self.can_reload = false;
+        self.set_scalar_fields(&[]);
+        self.set_views(&[]);
+        self.set_origin([0.0; 3]);
+        self.set_clouds(&[]);
+        self.set_sequence(0);
+        self.set_fusion(0, FrameRange::spread(0));
+    }
+
+    pub fn set_scalar_fields(&mut self, scalar_fields: &[ScalarField]) {
+        self.scalar_fields = scalar_fields
+            .iter()
+            .map(|field| ScalarFieldInfo {
+                name: field.name.clone(),
+                range: field.range(),
+            })
+            .collect();
+        self.color_by = None;
+    }
+
+    pub fn set_clouds(&mut self, clouds: &[Cloud]) {
+        self.clouds = clouds.iter().map(|cloud| cloud.name.clone()).collect();
+        self.visible_clouds = vec![true; clouds.len()];
+    }
+
+    pub fn set_sequence(&mut self, frame_count: usize) {
+        self.frame_count = frame_count;
+        self.frame = 0;
+        self.playing = false;
+    }
+
+    pub fn set_fusion(&mut self, frame_count: usize, fused_range: FrameRange) {
+        self.fusion_frame_count = frame_count;
+        self.fusion_range = fused_range;
+        self.fused_range = fused_range;
+    }
+
+    /// Moves on to the next frame of the sequence, starting over after the last one.
+    pub fn next_frame(&mut self) {
+        if self.frame_count > 0 {
+            self.frame = (self.frame + 1) % self.frame_count;
+        }
+    }
+
+    pub fn set_origin(&mut self, origin: [f64; 3]) {
+        self.origin = origin;
+    }
+
+    pub fn set_views(&mut self, views: &[CameraView]) {
+        self.views = views.to_vec();
+        self.selected_view = None;
+    }
+
+    pub fn set_import_options(&mut self, options: &ImportOptions) {
+        self.text_columns = options
+            .text
+            .columns
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        self.text_delimiter = match options.text.delimiter {
+            Some('\t') => "\\t".into(),
+            Some(delimiter) => delimiter.to_string(),
+            None => String::new(),
+        };
+        self.color_range = options.text.color_range;
+        self.depth_enabled = options.depth.is_some();
+        if let Some(depth) = &options.depth {
+            self.depth_pinhole = depth.pinhole;
+            self.depth_scale_set = depth.depth_scale.is_some();
+            if let Some(depth_scale) = depth.depth_scale {
+                self.depth_scale = depth_scale;
+            }
+            self.depth_color = depth
+                .color_image
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+        }
+    }
+
+    /// Returns the import settings entered in the GUI.
+    pub fn import_options(&self) -> Result<ImportOptions, String> {
+        let mut options = ImportOptions::default();
+        if !self.text_columns.trim().is_empty() {
+            options.text.columns = Some(self.text_columns.parse()?);
+        }
+        if !self.text_delimiter.is_empty() {
+            options.text.delimiter = Some(parse_delimiter(&self.text_delimiter)?);
+        }
+        options.text.color_range = self.color_range;
+        if self.depth_enabled {
+            options.depth = Some(DepthOptions {
+                pinhole: self.depth_pinhole,
+                depth_scale: Some(self.depth_scale).filter(|_| self.depth_scale_set),
+                color_image: Some(self.depth_color.trim())
+                    .filter(|path| !path.is_empty())
+                    .map(Into::into),
+            });
+        }
+        Ok(options)
+    }
+
+    pub fn show_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    /// Shows an image, replacing the image of the same name. Returns the texture of the replaced
+    /// image, which isn't used anymore.
+    pub fn add_image(
+        &mut self,
+        name: String,
+        texture_id: TextureId,
+        size: (f32, f32),
+    ) -> Option<TextureId> {
+        let image = ImageTextureId {
+            name,
             texture_id,
             size: size.into(),
-        });
+        };
+        match self
+            .images
+            .iter_mut()
+            .find(|shown| shown.name == image.name)
+        {
+            Some(shown) => Some(std::mem::replace(shown, image).texture_id),
+            None => {
+                self.images.push(image);
+                None
+            }
+        }
     }
 
     pub fn ui(&mut self, ui: &mut Ui) {
@@ -68,9 +340,42 @@ impl Interface {
     }
 
     fn options_ui(&mut self, ui: &mut Ui) {
-        if self.file_name.is_empty() {}
+        if let Some(progress) = self.import_progress {
+            ui.horizontal(|ui| {
+                if ui.button("Cancel").clicked() {
+                    self.cancel_requested = true;
+                }
+                ui.add(ProgressBar::new(progress).show_percentage().animate(true));
+            });
+        }
+        if self.origin != [0.0; 3] {
+            let [x, y, z] = self.origin;
+            ui.label(format!("Origin: {} {} {}", x, y, z));
+        }
         if ui.add(Button::new("File")).clicked() {
-            // do nothing right now
+            self.export_open = !self.export_open;
+        }
+        if !self.layers.is_empty() {
+            CollapsingHeader::new(format!("Layers ({})", self.layers.len()))
+                .default_open(true)
+                .show(ui, |ui| {
+                    let active_layer = &mut self.active_layer;
+                    let removed_layer = &mut self.removed_layer;
+                    for (i, layer) in self.layers.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut layer.visible, "").on_hover_text("visible");
+                            if ui
+                                .selectable_label(*active_layer == i, &layer.name)
+                                .clicked()
+                            {
+                                *active_layer = i;
+                            }
+                            if ui.small_button("x").on_hover_text("remove").clicked() {
+                                *removed_layer = Some(i);
+                            }
+                        });
+                    }
+                });
         }
         ui.add(
             Slider::new(
@@ -90,6 +395,169 @@ impl Interface {
         ui.label(format!("Window width: {}", self.window_width));
         ui.label(format!("Window height: {}", self.window_height));
 
+        if !self.scalar_fields.is_empty() {
+            CollapsingHeader::new("Fields").show(ui, |ui| {
+                ui.label("Color by");
+                ui.radio_value(&mut self.color_by, None, "color");
+                for (i, field) in self.scalar_fields.iter().enumerate() {
+                    let text = match field.range {
+                        Some((min, max)) => format!("{}: {} to {}", field.name, min, max),
+                        None => field.name.clone(),
+                    };
+                    ui.radio_value(&mut self.color_by, Some(i), text);
+                }
+            });
+        }
+
+        if self.frame_count > 1 {
+            CollapsingHeader::new("Sequence")
+                .default_open(true)
+                .show(ui, |ui| {
+                    ui.add(
+                        Slider::new(&mut self.frame, 0..=self.frame_count - 1)
+                            .text("frame")
+                            .clamp_to_range(true),
+                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("<").clicked() {
+                            self.frame = self.frame.saturating_sub(1);
+                        }
+                        let play_text = if self.playing { "Pause" } else { "Play" };
+                        if ui.button(play_text).clicked() {
+                            self.playing = !self.playing;
+                        }
+                        if ui.button(">").clicked() {
+                            self.frame = (self.frame + 1).min(self.frame_count - 1);
+                        }
+                    });
+                });
+        }
+
+        if self.fusion_frame_count > 0 {
+            CollapsingHeader::new("Fusion")
+                .default_open(true)
+                .show(ui, |ui| {
+                    let last = self.fusion_frame_count - 1;
+                    let range = &mut self.fusion_range;
+                    ui.add(
+                        Slider::new(&mut range.start, 0..=last)
+                            .text("first frame")
+                            .clamp_to_range(true),
+                    );
+                    ui.add(
+                        Slider::new(&mut range.end, range.start..=last)
+                            .text("last frame")
+                            .clamp_to_range(true),
+                    );
+                    ui.add(
+                        Slider::new(&mut range.step, 1..=last.max(1))
+                            .text("step")
+                            .clamp_to_range(true)
+                            .logarithmic(true),
+                    );
+                    ui.label(format!(
+                        "{} of {} frames fused",
+                        self.fused_range.indices().count(),
+                        self.fusion_frame_count
+                    ));
+                    if ui
+                        .add_enabled(*range != self.fused_range, Button::new("Fuse"))
+                        .clicked()
+                    {
+                        self.fused_range = *range;
+                    }
+                });
+        }
+
+        if self.clouds.len() > 1 {
+            CollapsingHeader::new(format!("Clouds ({})", self.clouds.len())).show(ui, |ui| {
+                for (name, visible) in self.clouds.iter().zip(&mut self.visible_clouds) {
+                    ui.checkbox(visible, name);
+                }
+            });
+        }
+
+        CollapsingHeader::new("Text import").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("columns");
+                ui.text_edit_singleline(&mut self.text_columns)
+                    .on_hover_text(
+                        "e.g. x,y,z,r,g,b or x y z _ intensity\nempty to use the header",
+                    );
+            });
+            ui.horizontal(|ui| {
+                ui.label("delimiter");
+                ui.text_edit_singleline(&mut self.text_delimiter)
+                    .on_hover_text("empty to detect");
+            });
+            ui.horizontal(|ui| {
+                ui.label("colors");
+                ui.radio_value(&mut self.color_range, ColorRange::Auto, "auto");
+                ui.radio_value(&mut self.color_range, ColorRange::Unit, "0-1");
+                ui.radio_value(&mut self.color_range, ColorRange::Byte, "0-255");
+            });
+            if self.can_reload && ui.button("Reload").clicked() {
+                self.reload_requested = true;
+            }
+        });
+
+        CollapsingHeader::new("Depth images").show(ui, |ui| {
+            ui.checkbox(&mut self.depth_enabled, "back-project images as depth");
+            ui.horizontal(|ui| {
+                let pinhole = &mut self.depth_pinhole;
+                ui.add(DragValue::new(&mut pinhole.fx).prefix("fx "));
+                ui.add(DragValue::new(&mut pinhole.fy).prefix("fy "));
+                ui.add(DragValue::new(&mut pinhole.cx).prefix("cx "));
+                ui.add(DragValue::new(&mut pinhole.cy).prefix("cy "));
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.depth_scale_set, "values per meter");
+                if self.depth_scale_set {
+                    ui.add(
+                        DragValue::new(&mut self.depth_scale).clamp_range(f32::EPSILON..=f32::MAX),
+                    );
+                } else {
+                    ui.label("1000, or 5000 for TUM datasets");
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("color image");
+                ui.text_edit_singleline(&mut self.depth_color);
+            });
+            if self.can_reload && ui.button("Reload").clicked() {
+                self.reload_requested = true;
+            }
+        });
+
+        if !self.views.is_empty() {
+            CollapsingHeader::new(format!("Views ({})", self.views.len())).show(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (i, view) in self.views.iter().enumerate() {
+                            let intrinsics = &view.intrinsics;
+                            let clicked = ui
+                                .button(&view.name)
+                                .on_hover_text(format!(
+                                    "{} {}x{}\nfocal: {} {}\nprincipal point: {} {}\ndistortion: {:?}",
+                                    intrinsics.model,
+                                    intrinsics.width,
+                                    intrinsics.height,
+                                    intrinsics.fx,
+                                    intrinsics.fy,
+                                    intrinsics.cx,
+                                    intrinsics.cy,
+                                    intrinsics.distortion,
+                                ))
+                                .clicked();
+                            if clicked {
+                                self.selected_view = Some(i);
+                            }
+                        }
+                    });
+            });
+        }
+
         if self.images.len() > 1 {
             ui.add(
                 Slider::new(
@@ -102,12 +570,36 @@ impl Interface {
             );
         }
         if let Some(img) = self.images.get(self.displayed_image_idx as usize) {
+            ui.label(&img.name);
             let width = 360f32;
             let height = img.size.y * (width / img.size.x);
             ui.image(img.texture_id, (width, height));
         }
     }
 
+    fn export_ui(&mut self, ui: &mut Ui) {
+        ui.label("Writes the visible layers into one file.");
+        ui.horizontal(|ui| {
+            ui.label("file");
+            ui.text_edit_singleline(&mut self.export_path)
+                .on_hover_text(
+                    "the format is taken from the extension: ply, pcd, las, laz, xyz, csv or txt",
+                );
+        });
+        ui.horizontal(|ui| {
+            ui.label("encoding").on_hover_text("used by ply and pcd");
+            ui.radio_value(&mut self.export_encoding, Encoding::Binary, "binary");
+            ui.radio_value(&mut self.export_encoding, Encoding::Ascii, "ascii");
+        });
+        let can_export = !self.layers.is_empty() && !self.export_path.trim().is_empty();
+        if ui.add_enabled(can_export, Button::new("Save")).clicked() {
+            self.export_requested = true;
+        }
+        if !self.export_status.is_empty() {
+            ui.label(&self.export_status);
+        }
+    }
+
     pub fn input(&mut self, event: &WindowEvent<'_>, window: &Window) {
         // capture mouse-move and btn-release as `DeviceEvent`s so we can see them when the pointer leaves the screen
         match event {
@@ -131,7 +623,7 @@ impl epi::App for Interface {
         "⛅ Ennona"
     }
 
-    /// Called by the framework to load old app state (if any).
+    // Called by the framework to load old app state (if any).
     // #[cfg(feature = "persistence")]
     // fn setup(
     //     &mut self,
@@ -142,7 +634,7 @@ impl epi::App for Interface {
     //     *self = epi::get_value(storage.unwrap(), epi::APP_KEY).unwrap_or_default()
     // }
 
-    /// Called by the frame work to save state before shutdown.
+    // Called by the frame work to save state before shutdown.
     // #[cfg(feature = "persistence")]
     // fn save(&mut self, storage: &mut dyn epi::Storage) {
     //     epi::set_value(storage, epi::APP_KEY, self);
@@ -152,5 +644,23 @@ impl epi::App for Interface {
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame<'_>) {
         egui::Area::new("settings").show(ctx, |ui| self.ui(ui));
+
+        let mut export_open = self.export_open;
+        egui::Window::new("Export")
+            .open(&mut export_open)
+            .collapsible(false)
+            .show(ctx, |ui| self.export_ui(ui));
+        self.export_open = export_open;
+
+        if let Some(error) = &self.error {
+            let mut open = true;
+            egui::Window::new("Error")
+                .open(&mut open)
+                .collapsible(false)
+                .show(ctx, |ui| ui.label(error));
+            if !open {
+                self.error = None;
+            }
+        }
     }
 }
@@ -0,0 +1,32 @@
+//! Point cloud viewer and reconstruction tool for rust-cv.
+//!
+//! Besides the `ennona` viewer, the crate can be used to load point clouds, meshes and
+//! reconstructions in other programs:
+//!
+//! ```no_run
+//! use ennona::import::{self, progress::Progress, Import, ImportOptions};
+//!
+//! let import = import::import(
+//!     "scan.ply".as_ref(),
+//!     &ImportOptions::default(),
+//!     &Progress::default(),
+//! )?;
+//! if let Import::Ply(ply_data) = import {
+//!     println!("{} points", ply_data.point_vertices.len());
+//! }
+//! # Ok::<(), import::ImportError>(())
+//! ```
+
+pub mod camera;
+pub mod export;
+pub mod import;
+pub mod interface;
+pub mod points;
+pub mod state;
+pub mod viewer;
+
+pub use camera::{Camera, CameraController};
+pub use import::{Import, ImportError, ImportOptions, PlyData};
+pub use points::Vertex;
+pub use state::State;
+pub use viewer::Viewer;
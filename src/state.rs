@@ -2,14 +2,14 @@ mod face_renderer;
 mod gui_renderer;
 mod point_renderer;
 
-use crate::{import::PlyData, Camera, Interface};
+use crate::{camera::Camera, import::PlyData, interface::Interface};
 use bytemuck::{Pod, Zeroable};
 use egui::TextureId;
-use face_renderer::FaceRenderer;
+use face_renderer::{FaceBuffers, FaceRenderer};
 use gui_renderer::GuiRenderer;
 use image::DynamicImage;
 use nalgebra::Matrix4;
-use point_renderer::PointRenderer;
+use point_renderer::{PointBuffers, PointRenderer};
 use wgpu::{
     util::DeviceExt, BindGroup, Buffer, Color, Device, Queue, Surface, SurfaceConfiguration,
     SurfaceError,
@@ -27,6 +27,14 @@ pub struct State {
     point_renderer: PointRenderer,
     face_renderer: FaceRenderer,
     gui_renderer: GuiRenderer,
+    layers: Vec<Layer>,
+}
+
+/// The GPU buffers of one loaded file, which are rendered together with all other layers.
+struct Layer {
+    points: Option<PointBuffers>,
+    faces: Option<FaceBuffers>,
+    visible: bool,
 }
 
 impl State {
@@ -126,6 +134,7 @@ impl State {
             point_renderer,
             face_renderer,
             gui_renderer,
+            layers: Vec::new(),
         }
     }
 
@@ -169,8 +178,12 @@ impl State {
         let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
             label: Some("Compute Pass"),
         });
-        self.point_renderer
-            .compute(&mut compute_pass, &self.uniform_bind_group);
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            if let Some(points) = &layer.points {
+                self.point_renderer
+                    .compute(&mut compute_pass, &self.uniform_bind_group, points);
+            }
+        }
         drop(compute_pass);
 
         // Clears the screen to black when this render pass executes.
@@ -187,9 +200,15 @@ impl State {
             }],
             depth_stencil_attachment: None,
         });
-        self.point_renderer.render(&mut render_pass);
-        self.face_renderer
-            .render(&mut render_pass, &self.uniform_bind_group);
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            if let Some(points) = &layer.points {
+                self.point_renderer.render(&mut render_pass, points);
+            }
+            if let Some(faces) = &layer.faces {
+                self.face_renderer
+                    .render(&mut render_pass, &self.uniform_bind_group, faces);
+            }
+        }
         drop(render_pass);
 
         // Render the GUI. This internally creates a new render pass.
@@ -212,10 +231,28 @@ impl State {
         Ok(())
     }
 
-    /// Imports data from a PLY file.
-    pub fn import_ply(&mut self, ply: &PlyData) {
-        self.point_renderer.import_ply(&self.device, ply);
-        self.face_renderer.import_ply(&self.device, ply);
+    /// Uploads data from a PLY file as a new layer on top of the others.
+    pub fn add_layer(&mut self, ply: &PlyData) {
+        self.layers.push(Layer {
+            points: self.point_renderer.import_ply(&self.device, ply),
+            faces: self.face_renderer.import_ply(&self.device, ply),
+            visible: true,
+        });
+    }
+
+    /// Replaces the data of a layer, keeping its visibility.
+    pub fn update_layer(&mut self, index: usize, ply: &PlyData) {
+        let layer = &mut self.layers[index];
+        layer.points = self.point_renderer.import_ply(&self.device, ply);
+        layer.faces = self.face_renderer.import_ply(&self.device, ply);
+    }
+
+    pub fn remove_layer(&mut self, index: usize) {
+        self.layers.remove(index);
+    }
+
+    pub fn set_layer_visible(&mut self, index: usize, visible: bool) {
+        self.layers[index].visible = visible;
     }
 
     /// Creates a texture to use with `egui`.
@@ -223,8 +260,13 @@ impl State {
         self.gui_renderer.make_texture(image)
     }
 
+    /// Frees a texture made with [`State::make_egui_texture`] that isn't shown anymore.
+    pub fn free_egui_texture(&mut self, texture: TextureId) {
+        self.gui_renderer.free_texture(texture);
+    }
+
     /// Returns if the event was caputured by the gui.
-    pub fn handle_event(&mut self, event: &Event<'_, ()>) -> bool {
+    pub fn handle_event<T>(&mut self, event: &Event<'_, T>) -> bool {
         self.gui_renderer.handle_event(event)
     }
 }
@@ -7,6 +7,10 @@ use crate::{import::PlyData, points::Vertex};
 
 pub struct FaceRenderer {
     render_pipeline: RenderPipeline,
+}
+
+/// The GPU buffers holding the faces of one layer.
+pub struct FaceBuffers {
     vertices: Buffer,
     indices: Buffer,
     num_indices: u32,
@@ -53,59 +57,42 @@ impl FaceRenderer {
             multisample: wgpu::MultisampleState::default(),
         });
 
-        // Create the buffer for the face verticies.
-        let vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Face Vertex Buffer"),
-            contents: bytemuck::cast_slice(&[Vertex {
-                position: [0.0, 0.0, 0.0],
-                _padding0: [0; 4],
-                color: [0.0, 0.0, 0.0],
-                _padding1: [0; 4],
-            }]),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-
-        // Create the buffer for the face indices.
-        let indices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Face Index Buffer"),
-            contents: &[],
-            usage: wgpu::BufferUsages::INDEX,
-        });
-
         // Store everything in the renderer.
-        Self {
-            render_pipeline,
-            vertices,
-            indices,
-            num_indices: 0,
-        }
+        Self { render_pipeline }
     }
 
     pub fn render<'a>(
         &'a self,
         render_pass: &mut RenderPass<'a>,
         uniform_bind_group: &'a BindGroup,
+        buffers: &'a FaceBuffers,
     ) {
-        if self.num_indices != 0 {
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, uniform_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertices.slice(..));
-            render_pass.set_index_buffer(self.indices.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
-        }
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, uniform_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffers.vertices.slice(..));
+        render_pass.set_index_buffer(buffers.indices.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..buffers.num_indices, 0, 0..1);
     }
 
-    pub fn import_ply(&mut self, device: &Device, ply: &PlyData) {
-        self.vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    /// Uploads the faces of the PLY data, returning `None` if there are none.
+    pub fn import_ply(&self, device: &Device, ply: &PlyData) -> Option<FaceBuffers> {
+        if ply.face_indices.is_empty() {
+            return None;
+        }
+        let vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Face Vertex Buffer"),
             contents: bytemuck::cast_slice(&ply.face_vertices),
             usage: wgpu::BufferUsages::VERTEX,
         });
-        self.num_indices = ply.face_indices.len() as u32;
-        self.indices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        let indices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Face Index Buffer"),
             contents: bytemuck::cast_slice(&ply.face_indices),
             usage: wgpu::BufferUsages::INDEX,
         });
+        Some(FaceBuffers {
+            vertices,
+            indices,
+            num_indices: ply.face_indices.len() as u32,
+        })
     }
 }
@@ -25,8 +25,8 @@ impl GuiRenderer {
         size: PhysicalSize<u32>,
     ) -> Self {
         let platform = Platform::new(PlatformDescriptor {
-            physical_width: size.width as u32,
-            physical_height: size.height as u32,
+            physical_width: size.width,
+            physical_height: size.height,
             scale_factor: window.scale_factor(),
             font_definitions: FontDefinitions::default(),
             style: Default::default(),
@@ -117,8 +117,12 @@ impl GuiRenderer {
             .alloc_srgba_premultiplied((width as usize, height as usize), &srgba_pixels[..])
     }
 
+    pub fn free_texture(&mut self, texture: TextureId) {
+        self.egui_render_pass.free(texture);
+    }
+
     /// Returns if the event was caputured by the gui.
-    pub fn handle_event(&mut self, event: &Event<'_, ()>) -> bool {
+    pub fn handle_event<T>(&mut self, event: &Event<'_, T>) -> bool {
         self.platform.handle_event(event);
         self.platform.captures_event(event)
     }
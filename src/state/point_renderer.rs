@@ -7,10 +7,15 @@ use crate::{import::PlyData, points::Vertex};
 
 pub struct PointRenderer {
     expander_bind_group_layout: BindGroupLayout,
-    expander_bind_group: BindGroup,
     expander_pipeline: ComputePipeline,
     render_pipeline: RenderPipeline,
-    point_vertices: Buffer,
+}
+
+/// The GPU buffers holding the points of one layer.
+pub struct PointBuffers {
+    expander_bind_group: BindGroup,
+    // The buffers are only referenced by the bind group, but have to be kept alive with it.
+    _point_vertices: Buffer,
     triangle_vertices: Buffer,
     num_points: u32,
 }
@@ -21,54 +26,17 @@ impl PointRenderer {
         uniform_bind_group_layout: &BindGroupLayout,
         target_texture_format: TextureFormat,
     ) -> Self {
-        // Create an initial dummy buffer for the point verticies.
-        let point_vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Point Vertex Buffer"),
-            contents: bytemuck::cast_slice(&[Vertex {
-                position: [0.0, 0.0, 0.0],
-                _padding0: [0; 4],
-                color: [0.0, 0.0, 0.0],
-                _padding1: [0; 4],
-            }]),
-            usage: wgpu::BufferUsages::STORAGE,
-        });
-
-        // Create an initial dummy buffer for the triangle verticies.
-        let triangle_vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Triangle Vertex Buffer"),
-            contents: bytemuck::cast_slice(&[Vertex {
-                position: [0.0, 0.0, 0.0],
-                _padding0: [0; 4],
-                color: [0.0, 0.0, 0.0],
-                _padding1: [0; 4],
-            }]),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
-        });
-
         // Create the bind group layout and the pipeline for the point to triangle expansion compute shader.
         let (expander_bind_group_layout, expander_pipeline) =
             point_expander_pipeline(device, uniform_bind_group_layout);
 
-        // Create the inital bind group (this needs to be updated whenever the buffers are updated)
-        // that binds the point and triangle verticies to be used in the expander pipeline.
-        let expander_bind_group = expander_bind_group(
-            device,
-            &expander_bind_group_layout,
-            &point_vertices,
-            &triangle_vertices,
-        );
-
         // Create the render pipeline which takes the triangles and renders them to draw the points (tiny triangles).
         let render_pipeline = render_pipeline(device, target_texture_format);
 
         Self {
             expander_bind_group_layout,
-            expander_bind_group,
             expander_pipeline,
             render_pipeline,
-            point_vertices,
-            triangle_vertices,
-            num_points: 0,
         }
     }
 
@@ -76,55 +44,59 @@ impl PointRenderer {
         &'a self,
         compute_pass: &mut ComputePass<'a>,
         uniform_bind_group: &'a BindGroup,
+        buffers: &'a PointBuffers,
     ) {
-        if self.num_points != 0 {
-            // Add the compute pipeline.
-            compute_pass.set_pipeline(&self.expander_pipeline);
-            compute_pass.set_bind_group(0, uniform_bind_group, &[]);
-            compute_pass.set_bind_group(1, &self.expander_bind_group, &[]);
-            compute_pass.dispatch((self.num_points as f64 / 64.0).ceil() as u32, 1, 1);
-        }
+        // Add the compute pipeline.
+        compute_pass.set_pipeline(&self.expander_pipeline);
+        compute_pass.set_bind_group(0, uniform_bind_group, &[]);
+        compute_pass.set_bind_group(1, &buffers.expander_bind_group, &[]);
+        compute_pass.dispatch((buffers.num_points as f64 / 64.0).ceil() as u32, 1, 1);
     }
 
-    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
-        if self.num_points != 0 {
-            // Add the render pipeline.
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_vertex_buffer(0, self.triangle_vertices.slice(..));
-            render_pass.draw(0..self.num_points * 3, 0..1);
-        }
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, buffers: &'a PointBuffers) {
+        // Add the render pipeline.
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, buffers.triangle_vertices.slice(..));
+        render_pass.draw(0..buffers.num_points * 3, 0..1);
     }
 
-    pub fn import_ply(&mut self, device: &Device, ply: &PlyData) {
-        // Update the number of points and exit if there are none.
-        self.num_points = ply.point_vertices.len() as u32;
-        if self.num_points == 0 {
-            return;
+    /// Uploads the points of the PLY data, returning `None` if there are none.
+    pub fn import_ply(&self, device: &Device, ply: &PlyData) -> Option<PointBuffers> {
+        let num_points = ply.point_vertices.len() as u32;
+        if num_points == 0 {
+            return None;
         }
 
-        // Update the point verticies from the PLY data.
+        // Upload the point verticies from the PLY data.
         let contents = bytemuck::cast_slice(&ply.point_vertices);
-        self.point_vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        let point_vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Point Vertex Buffer"),
             contents,
             usage: wgpu::BufferUsages::STORAGE,
         });
 
         // Create a sufficiently large buffer for the triangle verticies.
-        self.triangle_vertices = device.create_buffer(&wgpu::BufferDescriptor {
+        let triangle_vertices = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Triangle Vertex Buffer"),
             size: (contents.len() * 3) as u64,
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
             mapped_at_creation: false,
         });
 
-        // Since we just created brand new buffers, we need to recreate the bind group.
-        self.expander_bind_group = expander_bind_group(
+        // Bind the point and triangle verticies to be used in the expander pipeline.
+        let expander_bind_group = expander_bind_group(
             device,
             &self.expander_bind_group_layout,
-            &self.point_vertices,
-            &self.triangle_vertices,
+            &point_vertices,
+            &triangle_vertices,
         );
+
+        Some(PointBuffers {
+            expander_bind_group,
+            _point_vertices: point_vertices,
+            triangle_vertices,
+            num_points,
+        })
     }
 }
 
@@ -0,0 +1,1070 @@
+//! The viewer window, which shows imported files and data sent from other threads.
+//!
+//! Programs that compute point clouds, such as structure from motion or SLAM pipelines, can show
+//! their intermediate results without writing them to files:
+//!
+//! ```no_run
+//! use ennona::{import::PlyData, viewer::{Message, Viewer}};
+//! # fn reconstruct(step: usize) -> PlyData { PlyData::default() }
+//!
+//! let viewer = Viewer::new()?;
+//! let sender = viewer.sender();
+//! std::thread::spawn(move || {
+//!     for step in 0..100 {
+//!         // the state of the reconstruction after another step of it
+//!         let data = reconstruct(step);
+//!         let message = Message::Layer { name: "reconstruction".into(), data };
+//!         if sender.send(message).is_err() {
+//!             // the window was closed
+//!             break;
+//!         }
+//!     }
+//! });
+//! viewer.run();
+//! # Ok::<(), winit::error::OsError>(())
+//! ```
+
+use crate::{
+    camera::Camera,
+    export::{self, ExportError, ExportOptions},
+    import::{
+        self,
+        progress::Progress,
+        tum::{FrameRange, RgbdDataset},
+        CameraView, Import, ImportError, ImportErrorKind, ImportOptions, PlyData, Sequence,
+    },
+    interface::Interface,
+    state::State,
+};
+use futures_lite::future::block_on;
+use image::{DynamicImage, GenericImageView};
+use nalgebra::Vector3;
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use wgpu::SurfaceError;
+use winit::{
+    dpi::PhysicalPosition,
+    error::OsError,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
+    platform::run_return::EventLoopExtRunReturn,
+    window::{Window, WindowBuilder},
+};
+
+const INITIAL_WIDTH: u32 = 1920;
+const INITIAL_HEIGHT: u32 = 1080;
+
+// KITTI scans are recorded at 10 Hz
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Data that is shown in a viewer, sent from another thread through a [`ViewerSender`].
+pub enum Message {
+    /// Shows a point cloud or mesh as a layer, replacing the layer of the same name.
+    ///
+    /// The camera is only pointed at the data when the layer is new, so a layer can be updated
+    /// continuously while it is looked at.
+    Layer { name: String, data: PlyData },
+    /// Replaces the camera poses of a layer, such as the registered images of a reconstruction.
+    Views {
+        layer: String,
+        views: Vec<CameraView>,
+    },
+    /// Shows an image in the GUI, replacing the image of the same name, such as the previous
+    /// frame of a camera.
+    Image { name: String, image: DynamicImage },
+    /// Removes the layer of this name, if there is one.
+    RemoveLayer(String),
+}
+
+impl Message {
+    /// Returns if this message makes an `earlier` one that wasn't shown yet pointless.
+    fn replaces(&self, earlier: &Message) -> bool {
+        match (self, earlier) {
+            (
+                Message::Layer { name, .. } | Message::RemoveLayer(name),
+                Message::Layer { name: earlier, .. },
+            ) => name == earlier,
+            // new data of a layer comes with its own views
+            (
+                Message::Layer { name, .. }
+                | Message::Views { layer: name, .. }
+                | Message::RemoveLayer(name),
+                Message::Views { layer: earlier, .. },
+            ) => name == earlier,
+            (Message::Image { name, .. }, Message::Image { name: earlier, .. }) => name == earlier,
+            _ => false,
+        }
+    }
+}
+
+/// The viewer window, which runs the event loop on the main thread.
+pub struct Viewer {
+    event_loop: EventLoop<ViewerEvent>,
+    session: Session,
+}
+
+impl Viewer {
+    /// Opens the window.
+    ///
+    /// Like every window, the viewer has to be created on the main thread.
+    pub fn new() -> Result<Viewer, OsError> {
+        let event_loop = EventLoop::with_user_event();
+        let window = WindowBuilder::new()
+            .with_decorations(true)
+            .with_resizable(true)
+            .with_transparent(false)
+            .with_title("⛅ Ennona")
+            .with_inner_size(winit::dpi::PhysicalSize {
+                width: INITIAL_WIDTH,
+                height: INITIAL_HEIGHT,
+            })
+            .build(&event_loop)?;
+
+        window.set_window_icon(None);
+
+        let state = block_on(State::new(&window));
+        let camera = Camera::new(window.inner_size());
+        let app = Interface::new(
+            "".into(),
+            window.inner_size().height,
+            window.inner_size().width,
+        );
+        let now = Instant::now();
+        let session = Session {
+            mailbox: Arc::default(),
+            proxy: event_loop.create_proxy(),
+            window,
+            state,
+            camera,
+            app,
+            importing: None,
+            import_count: 0,
+            layers: Vec::new(),
+            shown_layer: None,
+            scene_origin: [0.0; 3],
+            last_frame_time: now,
+            last_update_time: now,
+            last_render_time: now,
+            mouse_position: None,
+        };
+        Ok(Viewer {
+            event_loop,
+            session,
+        })
+    }
+
+    /// Sets the options that files are imported with, which can be changed in the GUI as well.
+    pub fn set_import_options(&mut self, options: &ImportOptions) {
+        self.session.app.set_import_options(options);
+    }
+
+    /// Starts importing a file, which is shown once the event loop runs.
+    pub fn open(&mut self, path: &Path) {
+        self.session.start_import(path);
+    }
+
+    /// Returns a sender that other threads show data in this viewer with.
+    pub fn sender(&self) -> ViewerSender {
+        ViewerSender {
+            mailbox: self.session.mailbox.clone(),
+            proxy: self.event_loop.create_proxy(),
+        }
+    }
+
+    /// Shows the window until it is closed, and returns once it is, so the program can go on
+    /// afterwards.
+    pub fn run(self) {
+        let Viewer {
+            mut event_loop,
+            mut session,
+        } = self;
+        event_loop.run_return(|event, _, control_flow| session.handle_event(event, control_flow));
+    }
+}
+
+/// Sends data to a [`Viewer`], from any thread.
+///
+/// Messages wait in a mailbox until the viewer picks them up. Only the latest data of a layer or
+/// image is kept in there, so a sender that is faster than the viewer doesn't pile up data.
+#[derive(Clone)]
+pub struct ViewerSender {
+    mailbox: Arc<Mutex<Mailbox>>,
+    proxy: EventLoopProxy<ViewerEvent>,
+}
+
+impl ViewerSender {
+    /// Sends a message that the viewer picks up on its next update.
+    pub fn send(&self, message: Message) -> Result<(), ViewerClosed> {
+        let mut mailbox = self.mailbox.lock().unwrap_or_else(PoisonError::into_inner);
+        if mailbox.closed {
+            return Err(ViewerClosed);
+        }
+        let was_empty = mailbox.messages.is_empty();
+        mailbox
+            .messages
+            .retain(|earlier| !message.replaces(earlier));
+        mailbox.messages.push(message);
+        drop(mailbox);
+        // the viewer empties the whole mailbox once it wakes up
+        if was_empty {
+            self.proxy
+                .send_event(ViewerEvent::Mail)
+                .map_err(|_| ViewerClosed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Messages that were sent to a viewer, but not picked up yet.
+#[derive(Default)]
+struct Mailbox {
+    messages: Vec<Message>,
+    /// Set once the viewer is gone.
+    closed: bool,
+}
+
+/// The error of sending to a viewer whose window was closed.
+#[derive(Debug, Error)]
+#[error("the viewer was closed")]
+pub struct ViewerClosed;
+
+/// Messages from other threads to the event loop.
+enum ViewerEvent {
+    /// Progress of the import running on a worker thread.
+    Progress { job: usize, fraction: f32 },
+    /// The result of an import running on a worker thread.
+    Finished {
+        job: usize,
+        path: PathBuf,
+        result: Result<Imported, ImportError>,
+    },
+    /// Other frames of the layer from `source`, read on a worker thread for `settings`.
+    FramesLoaded {
+        job: usize,
+        source: Source,
+        settings: DisplaySettings,
+        result: Result<PlyData, ImportError>,
+    },
+    /// Messages from a [`ViewerSender`] are waiting in the mailbox.
+    Mail,
+}
+
+/// An imported file, ready to be shown.
+enum Imported {
+    Layer(Box<Loaded>),
+    Image(DynamicImage),
+}
+
+/// An import running on a worker thread.
+struct ImportJob {
+    id: usize,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ImportJob {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Where the data of a layer came from.
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    File(PathBuf),
+    /// Data sent through a [`ViewerSender`], with the name of its layer.
+    Sent(String),
+}
+
+impl Source {
+    /// Name of the layer in the GUI.
+    fn layer_name(&self) -> String {
+        match self {
+            Source::File(path) if path == Path::new(import::STDIN_PATH) => "standard input".into(),
+            Source::File(path) => path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+            Source::Sent(name) => name.clone(),
+        }
+    }
+
+    /// Returns the file to read the data of the layer from again, if it can be.
+    ///
+    /// Streams, like the standard input or named pipes, are gone once they were read, and data
+    /// sent by another program can't be read again either.
+    fn reload_path(&self) -> Option<&Path> {
+        match self {
+            Source::File(path)
+                if path != Path::new(import::STDIN_PATH) && (path.is_file() || path.is_dir()) =>
+            {
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Data that is shown as a layer, kept around to change how it is shown.
+struct Loaded {
+    source: Source,
+    /// The imported data, or the current frames of a sequence or dataset.
+    ply_data: PlyData,
+    frames: Option<Frames>,
+    /// The settings its layer was last uploaded with.
+    shown: DisplaySettings,
+}
+
+impl Loaded {
+    fn new(source: Source, ply_data: PlyData, frames: Option<Frames>) -> Loaded {
+        let dataset_frames = match &frames {
+            Some(Frames::Rgbd(dataset)) => dataset.frames.len(),
+            _ => 0,
+        };
+        let shown = DisplaySettings {
+            color_by: None,
+            visible_clouds: vec![true; ply_data.clouds.len()],
+            frame: 0,
+            fused_range: FrameRange::spread(dataset_frames),
+        };
+        Loaded {
+            source,
+            ply_data,
+            frames,
+            shown,
+        }
+    }
+}
+
+/// Frames of a layer that only some of are shown at a time, which are read on a worker thread
+/// when others are chosen.
+#[derive(Clone)]
+enum Frames {
+    Sequence(Arc<Sequence>),
+    Rgbd(Arc<RgbdDataset>),
+}
+
+impl Frames {
+    /// Returns if other frames have to be read to show the layer with `settings`.
+    fn changed(&self, shown: &DisplaySettings, settings: &DisplaySettings) -> bool {
+        match self {
+            Frames::Sequence(_) => settings.frame != shown.frame,
+            Frames::Rgbd(_) => settings.fused_range != shown.fused_range,
+        }
+    }
+
+    fn load(
+        &self,
+        settings: &DisplaySettings,
+        progress: &Progress,
+    ) -> Result<PlyData, ImportError> {
+        match self {
+            Frames::Sequence(sequence) => sequence.load(settings.frame, progress),
+            Frames::Rgbd(dataset) => dataset.fuse(&settings.fused_range, progress),
+        }
+    }
+}
+
+/// GUI settings that affect the data uploaded to the GPU.
+#[derive(Clone, PartialEq)]
+struct DisplaySettings {
+    color_by: Option<usize>,
+    visible_clouds: Vec<bool>,
+    frame: usize,
+    fused_range: FrameRange,
+}
+
+impl DisplaySettings {
+    fn of(app: &Interface) -> Self {
+        DisplaySettings {
+            color_by: app.color_by,
+            visible_clouds: app.visible_clouds.clone(),
+            frame: app.frame,
+            fused_range: app.fused_range,
+        }
+    }
+}
+
+/// Everything the event loop works with.
+struct Session {
+    /// Messages from other threads, shared with every [`ViewerSender`].
+    mailbox: Arc<Mutex<Mailbox>>,
+    window: Window,
+    state: State,
+    camera: Camera,
+    app: Interface,
+    /// Files, and the frames of sequences and datasets, are read on worker threads, which report
+    /// back through the event loop. One of them runs at a time.
+    proxy: EventLoopProxy<ViewerEvent>,
+    importing: Option<ImportJob>,
+    import_count: usize,
+    /// The shown data is kept around to change how it is shown later, in the same order as the
+    /// layers of the GPU state and the GUI.
+    layers: Vec<Loaded>,
+    shown_layer: Option<usize>,
+    /// Origin of the coordinates that every layer is shown in, see [`PlyData::scene_origin`].
+    scene_origin: [f64; 3],
+    last_frame_time: Instant,
+    last_update_time: Instant,
+    last_render_time: Instant,
+    mouse_position: Option<PhysicalPosition<f64>>,
+}
+
+impl Session {
+    fn handle_event(&mut self, event: Event<'_, ViewerEvent>, control_flow: &mut ControlFlow) {
+        // Handle GUI events and if the GUI captures the event, we do not want to handle it ourselves,
+        // so return immediately in that case.
+        if self.state.handle_event(&event) {
+            return;
+        }
+
+        // Perform our own handling of events.
+        match event {
+            Event::RedrawRequested(_) => {
+                match self
+                    .state
+                    .render(&mut self.app, &self.camera, self.window.scale_factor())
+                {
+                    Ok(_) => {
+                        self.last_render_time = self.last_update_time;
+                    }
+                    // Resize surface if lost (not sure how) or outdated (probably synchronization error)
+                    Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                        self.resize();
+                        self.window.request_redraw();
+                    }
+                    // The system is out of memory, we should probably quit
+                    Err(SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
+                    // If there is a timeout, we should just request another redraw
+                    // and hopefully it will be able to draw next frame.
+                    Err(SurfaceError::Timeout) => {
+                        eprintln!("warning: there was a timeout");
+                        self.window.request_redraw();
+                    }
+                }
+            }
+            Event::MainEventsCleared => self.update(),
+            Event::WindowEvent {
+                event: ref window_event,
+                window_id,
+                ..
+            } if window_id == self.window.id() => {
+                match window_event {
+                    WindowEvent::CloseRequested => {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                        self.resize();
+                    }
+                    WindowEvent::DroppedFile(path) => self.start_import(path),
+                    WindowEvent::CursorMoved { position, .. } => {
+                        if let Some(old_pos) = self.mouse_position.replace(*position) {
+                            let delta_x = position.x - old_pos.x;
+                            let delta_y = position.y - old_pos.y;
+                            if self.app.camera_controller.mouse_captured {
+                                let size = self.window.inner_size();
+                                let center = PhysicalPosition {
+                                    x: size.width / 2,
+                                    y: size.height / 2,
+                                };
+
+                                if self.window.set_cursor_position(center).is_ok() {
+                                    self.mouse_position.replace(PhysicalPosition {
+                                        x: center.x as f64,
+                                        y: center.y as f64,
+                                    });
+                                }
+                                self.app.camera_controller.process_mouse(
+                                    &mut self.camera,
+                                    delta_x,
+                                    delta_y,
+                                );
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+
+                self.app.input(window_event, &self.window);
+            }
+            Event::UserEvent(ViewerEvent::Progress { job, fraction })
+                if self.importing.as_ref().map(|importing| importing.id) == Some(job) =>
+            {
+                self.app.import_progress = Some(fraction);
+            }
+            // events of imports that were replaced by a newer one are thrown away
+            Event::UserEvent(ViewerEvent::Finished { job, path, result })
+                if self.importing.as_ref().map(|importing| importing.id) == Some(job) =>
+            {
+                self.importing = None;
+                self.app.import_progress = None;
+                self.import_finished(path, result);
+            }
+            Event::UserEvent(ViewerEvent::FramesLoaded {
+                job,
+                source,
+                settings,
+                result,
+            }) if self.importing.as_ref().map(|importing| importing.id) == Some(job) => {
+                self.importing = None;
+                self.app.import_progress = None;
+                self.frames_loaded(source, settings, result);
+            }
+            Event::UserEvent(ViewerEvent::Mail) => {
+                let messages = std::mem::take(
+                    &mut self
+                        .mailbox
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .messages,
+                );
+                for message in messages {
+                    self.receive(message);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn resize(&mut self) {
+        let size = self.window.inner_size();
+        self.app.resize(size);
+        self.camera.resize(size);
+        self.state.resize(size);
+    }
+
+    /// Applies the changes made in the GUI since the last update.
+    fn update(&mut self) {
+        let now = Instant::now();
+        let dt = now - self.last_update_time;
+        self.last_update_time = now;
+        self.app.update_camera(&mut self.camera, dt);
+        if std::mem::take(&mut self.app.reload_requested) {
+            let path = self
+                .layers
+                .get(self.app.active_layer)
+                .and_then(|layer| layer.source.reload_path())
+                .map(Path::to_owned);
+            match path {
+                Some(path) => self.start_import(&path),
+                None => log::warn!("the active layer can't be read again"),
+            }
+        }
+        if std::mem::take(&mut self.app.export_requested) {
+            match export_layers(&self.layers, &self.app) {
+                Ok(path) => self.app.export_status = format!("saved {}", path.display()),
+                Err(e) => {
+                    log::error!("{}", e);
+                    self.app.show_error(e.to_string());
+                }
+            }
+        }
+        if std::mem::take(&mut self.app.cancel_requested) {
+            if let Some(job) = &self.importing {
+                job.cancel();
+            }
+        }
+        if self.app.playing && self.last_frame_time.elapsed() >= FRAME_INTERVAL {
+            self.app.next_frame();
+            self.last_frame_time = now;
+        }
+        if let Some(removed) = self.app.removed_layer.take() {
+            self.remove_layer(removed);
+        }
+        for (i, layer) in self.app.layers.iter().enumerate() {
+            self.state.set_layer_visible(i, layer.visible);
+        }
+        let app = &mut self.app;
+        let mut frames_to_load = None;
+        if let Some(loaded) = self.layers.get_mut(app.active_layer) {
+            if self.shown_layer != Some(app.active_layer) {
+                show_layer(loaded, self.scene_origin, app);
+                self.shown_layer = Some(app.active_layer);
+            }
+            let settings = DisplaySettings::of(app);
+            if settings != loaded.shown {
+                match &loaded.frames {
+                    // the layer is uploaded once the frames are read, after the running import
+                    Some(frames) if frames.changed(&loaded.shown, &settings) => {
+                        if self.importing.is_none() {
+                            frames_to_load =
+                                Some((loaded.source.clone(), frames.clone(), settings));
+                        }
+                    }
+                    _ => {
+                        upload_layer(
+                            app.active_layer,
+                            &loaded.ply_data,
+                            &settings,
+                            self.scene_origin,
+                            &mut self.state,
+                        );
+                        loaded.shown = settings;
+                    }
+                }
+            }
+        }
+        if let Some((source, frames, settings)) = frames_to_load {
+            self.start_job(move |job, progress| ViewerEvent::FramesLoaded {
+                job,
+                result: frames.load(&settings, progress),
+                source,
+                settings,
+            });
+        }
+        if self.last_render_time.elapsed() >= Duration::from_millis(15) {
+            self.window.request_redraw();
+        }
+    }
+
+    /// Starts importing a file with the settings from the GUI on a worker thread, replacing the
+    /// import that is running. Invalid settings are reported in the GUI.
+    fn start_import(&mut self, path: &Path) {
+        if let Some(job) = self.importing.take() {
+            job.cancel();
+        }
+
+        let options = match self.app.import_options() {
+            Ok(options) => options,
+            Err(e) => {
+                let e = format!("invalid import settings: {}", e);
+                log::error!("{}", e);
+                self.app.show_error(e);
+                return;
+            }
+        };
+        let path = path.to_owned();
+        self.start_job(move |job, progress| {
+            let result = import::import(&path, &options, progress)
+                .and_then(|import| load_import(import, &path, progress));
+            ViewerEvent::Finished { job, path, result }
+        });
+    }
+
+    /// Runs `work` on a worker thread, replacing the import that is running. Its progress is
+    /// shown in the GUI, where it can be cancelled as well.
+    fn start_job(&mut self, work: impl FnOnce(usize, &Progress) -> ViewerEvent + Send + 'static) {
+        self.import_count += 1;
+        if let Some(job) = self.importing.take() {
+            job.cancel();
+        }
+        let id = self.import_count;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let progress_proxy = self.proxy.clone();
+        let progress = Progress::new(
+            move |fraction| {
+                // the event loop is gone when the window was closed during the import
+                let _ = progress_proxy.send_event(ViewerEvent::Progress { job: id, fraction });
+            },
+            cancelled.clone(),
+        );
+        let proxy = self.proxy.clone();
+        thread::spawn(move || {
+            let _ = proxy.send_event(work(id, &progress));
+        });
+        self.app.import_progress = Some(0.0);
+        self.importing = Some(ImportJob { id, cancelled });
+    }
+
+    fn import_finished(&mut self, path: PathBuf, result: Result<Imported, ImportError>) {
+        match result {
+            Ok(Imported::Layer(loaded)) => {
+                // opening a file again replaces its layer
+                let i = self.show_loaded(*loaded);
+                let ply_data = &self.layers[i].ply_data;
+                point_camera_at(ply_data, self.scene_origin, &mut self.camera, &mut self.app);
+            }
+            Ok(Imported::Image(image)) => {
+                // opening an image again replaces it
+                let name = Source::File(path).layer_name();
+                self.add_image(name, image);
+            }
+            Err(ImportError {
+                kind: ImportErrorKind::Cancelled,
+                ..
+            }) => log::info!("cancelled the import of '{}'", path.display()),
+            Err(e) => {
+                log::error!("{}", e);
+                self.app.show_error(e.to_string());
+            }
+        }
+    }
+
+    /// Shows the frames of a layer that were read for `settings`.
+    fn frames_loaded(
+        &mut self,
+        source: Source,
+        settings: DisplaySettings,
+        result: Result<PlyData, ImportError>,
+    ) {
+        // the layer may have been removed in the meantime
+        let i = match self.layers.iter().position(|layer| layer.source == source) {
+            Some(i) => i,
+            None => return,
+        };
+        let loaded = &mut self.layers[i];
+        let is_active = i == self.app.active_layer;
+        match result {
+            Ok(ply_data) => {
+                if is_active && matches!(loaded.frames, Some(Frames::Rgbd(_))) {
+                    self.app
+                        .set_views(&views_in_scene(&ply_data, self.scene_origin));
+                }
+                loaded.ply_data = ply_data;
+                upload_layer(
+                    i,
+                    &loaded.ply_data,
+                    &settings,
+                    self.scene_origin,
+                    &mut self.state,
+                );
+                loaded.shown = settings;
+            }
+            Err(ImportError {
+                kind: ImportErrorKind::Cancelled,
+                ..
+            }) => {
+                // go back to the shown frames, which would be read again otherwise
+                if is_active {
+                    self.app.frame = loaded.shown.frame;
+                    self.app.fused_range = loaded.shown.fused_range;
+                    self.app.playing = false;
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                self.app.show_error(e.to_string());
+                self.app.playing = false;
+                // the frames are not read again until others are chosen
+                loaded.shown.frame = settings.frame;
+                loaded.shown.fused_range = settings.fused_range;
+            }
+        }
+    }
+
+    /// Shows data sent from another thread.
+    fn receive(&mut self, message: Message) {
+        match message {
+            Message::Layer { name, data } => {
+                let source = Source::Sent(name);
+                // layers that are sent repeatedly often start out empty
+                let has_vertices = |ply_data: &PlyData| {
+                    !ply_data.point_vertices.is_empty() || !ply_data.face_vertices.is_empty()
+                };
+                let had_vertices = self
+                    .layers
+                    .iter()
+                    .any(|layer| layer.source == source && has_vertices(&layer.ply_data));
+                let is_first_data = !had_vertices && has_vertices(&data);
+                let i = self.show_loaded(Loaded::new(source, data, None));
+                if is_first_data {
+                    let ply_data = &self.layers[i].ply_data;
+                    point_camera_at(ply_data, self.scene_origin, &mut self.camera, &mut self.app);
+                }
+            }
+            Message::Views { layer, views } => {
+                let source = Source::Sent(layer);
+                match self.layers.iter().position(|layer| layer.source == source) {
+                    Some(i) => {
+                        self.layers[i].ply_data.views = views;
+                        if i == self.app.active_layer {
+                            let views = views_in_scene(&self.layers[i].ply_data, self.scene_origin);
+                            self.app.set_views(&views);
+                        }
+                    }
+                    None => log::warn!("there is no layer '{}'", source.layer_name()),
+                }
+            }
+            Message::Image { name, image } => self.add_image(name, image),
+            Message::RemoveLayer(name) => {
+                let source = Source::Sent(name);
+                if let Some(i) = self.layers.iter().position(|layer| layer.source == source) {
+                    self.remove_layer(i);
+                }
+            }
+        }
+    }
+
+    /// Adds a layer, or replaces the one with the same source, and returns its index.
+    fn show_loaded(&mut self, mut loaded: Loaded) -> usize {
+        let scene_origin = self.scene_origin;
+        let index = match self
+            .layers
+            .iter()
+            .position(|layer| layer.source == loaded.source)
+        {
+            Some(i) if matches!(loaded.source, Source::File(_)) => {
+                let ply_data = &loaded.ply_data;
+                upload_layer(i, ply_data, &loaded.shown, scene_origin, &mut self.state);
+                self.layers[i] = loaded;
+                self.app.active_layer = i;
+                self.shown_layer = None;
+                i
+            }
+            // sent data is updated in place, keeping how it is shown if the data still fits
+            Some(i) => {
+                let shown = &self.layers[i].shown;
+                let fits = shown
+                    .color_by
+                    .into_iter()
+                    .all(|field| field < loaded.ply_data.scalar_fields.len())
+                    && shown.visible_clouds.len() == loaded.ply_data.clouds.len();
+                if fits {
+                    loaded.shown = std::mem::replace(&mut self.layers[i].shown, loaded.shown);
+                }
+                let ply_data = &loaded.ply_data;
+                upload_layer(i, ply_data, &loaded.shown, scene_origin, &mut self.state);
+                self.layers[i] = loaded;
+                if self.app.active_layer == i {
+                    self.shown_layer = None;
+                }
+                i
+            }
+            None => {
+                let moved = moved_to(&loaded.ply_data, scene_origin);
+                self.state
+                    .add_layer(moved.as_ref().unwrap_or(&loaded.ply_data));
+                self.app.add_layer(loaded.source.layer_name());
+                self.layers.push(loaded);
+                self.shown_layer = None;
+                self.layers.len() - 1
+            }
+        };
+        self.update_scene_origin();
+        index
+    }
+
+    fn remove_layer(&mut self, index: usize) {
+        self.layers.remove(index);
+        self.state.remove_layer(index);
+        self.app.remove_layer(index);
+        self.shown_layer = None;
+        if self.layers.is_empty() {
+            self.app.clear_layer_settings();
+        }
+        self.update_scene_origin();
+    }
+
+    /// Moves the scene to the origin of its first layer that has one, uploading every layer
+    /// again if the origin changed.
+    fn update_scene_origin(&mut self) {
+        let origin = PlyData::scene_origin(self.layers.iter().map(|layer| &layer.ply_data));
+        if origin == self.scene_origin {
+            return;
+        }
+        self.scene_origin = origin;
+        for (i, layer) in self.layers.iter().enumerate() {
+            upload_layer(i, &layer.ply_data, &layer.shown, origin, &mut self.state);
+        }
+        self.app.set_origin(origin);
+        // the views of the active layer have moved as well
+        self.shown_layer = None;
+    }
+
+    fn add_image(&mut self, name: String, image: DynamicImage) {
+        let size = (image.width() as f32, image.height() as f32);
+        let texture = self.state.make_egui_texture(image);
+        if let Some(replaced) = self.app.add_image(name, texture, size) {
+            self.state.free_egui_texture(replaced);
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // senders find out on their next message
+        let mut mailbox = self.mailbox.lock().unwrap_or_else(PoisonError::into_inner);
+        mailbox.closed = true;
+        mailbox.messages.clear();
+    }
+}
+
+/// Turns an imported file into the data of a layer, reading the first frame of a sequence or
+/// fusing a dataset. Runs on the worker thread of the import.
+fn load_import(import: Import, path: &Path, progress: &Progress) -> Result<Imported, ImportError> {
+    let source = Source::File(path.to_owned());
+    Ok(match import {
+        Import::Ply(ply_data) => Imported::Layer(Box::new(Loaded::new(source, ply_data, None))),
+        Import::Sequence(sequence) => {
+            let ply_data = sequence.load(0, progress)?;
+            let frames = Frames::Sequence(Arc::new(sequence));
+            Imported::Layer(Box::new(Loaded::new(source, ply_data, Some(frames))))
+        }
+        Import::Rgbd(dataset) => {
+            let ply_data = dataset.fuse(&FrameRange::spread(dataset.frames.len()), progress)?;
+            let frames = Frames::Rgbd(Arc::new(dataset));
+            Imported::Layer(Box::new(Loaded::new(source, ply_data, Some(frames))))
+        }
+        Import::Image(image) => Imported::Image(image),
+    })
+}
+
+/// Writes the visible layers, without their hidden clouds, to the file entered in the GUI.
+fn export_layers(layers: &[Loaded], app: &Interface) -> Result<PathBuf, ExportError> {
+    let visible = layers
+        .iter()
+        .zip(&app.layers)
+        .filter(|(_, layer)| layer.visible)
+        .map(|(loaded, _)| loaded)
+        .collect::<Vec<_>>();
+    let without_hidden_clouds = visible
+        .iter()
+        .map(|loaded| {
+            let clouds = &loaded.shown.visible_clouds;
+            if clouds.iter().all(|&visible| visible) {
+                None
+            } else {
+                Some(loaded.ply_data.with_visible_clouds(clouds))
+            }
+        })
+        .collect::<Vec<_>>();
+    let parts = visible
+        .iter()
+        .zip(&without_hidden_clouds)
+        .map(|(loaded, filtered)| filtered.as_ref().unwrap_or(&loaded.ply_data))
+        .collect::<Vec<_>>();
+
+    let path = PathBuf::from(app.export_path.trim());
+    let options = ExportOptions {
+        encoding: app.export_encoding,
+    };
+    export::export(&path, &PlyData::merged(&parts), &options)?;
+    Ok(path)
+}
+
+/// Fills the GUI with the settings of a layer, as they were when it was last uploaded.
+fn show_layer(loaded: &Loaded, scene_origin: [f64; 3], app: &mut Interface) {
+    let ply_data = &loaded.ply_data;
+    app.set_scalar_fields(&ply_data.scalar_fields);
+    app.color_by = loaded.shown.color_by;
+    app.set_views(&views_in_scene(ply_data, scene_origin));
+    app.set_clouds(&ply_data.clouds);
+    app.visible_clouds = loaded.shown.visible_clouds.clone();
+    let (sequence_frames, dataset_frames) = match &loaded.frames {
+        Some(Frames::Sequence(sequence)) => (sequence.frames.len(), 0),
+        Some(Frames::Rgbd(dataset)) => (0, dataset.frames.len()),
+        None => (0, 0),
+    };
+    app.set_sequence(sequence_frames);
+    app.frame = loaded.shown.frame;
+    app.set_fusion(dataset_frames, loaded.shown.fused_range);
+    app.file_name = match &loaded.source {
+        Source::File(path) => path.display().to_string(),
+        Source::Sent(name) => name.clone(),
+    };
+    app.can_reload = loaded.source.reload_path().is_some();
+}
+
+/// Uploads the data of a layer as the GUI settings ask for: recolored, with hidden clouds left out
+/// and moved into the coordinates of the scene.
+fn upload_layer(
+    index: usize,
+    ply_data: &PlyData,
+    settings: &DisplaySettings,
+    scene_origin: [f64; 3],
+    state: &mut State,
+) {
+    let colored = settings
+        .color_by
+        .and_then(|i| ply_data.scalar_fields.get(i))
+        .map(|field| ply_data.colored_by(field));
+    let colored = colored.as_ref().unwrap_or(ply_data);
+    let filtered = if settings.visible_clouds.iter().all(|&visible| visible) {
+        None
+    } else {
+        Some(colored.with_visible_clouds(&settings.visible_clouds))
+    };
+    let filtered = filtered.as_ref().unwrap_or(colored);
+    let moved = moved_to(filtered, scene_origin);
+    state.update_layer(index, moved.as_ref().unwrap_or(filtered));
+}
+
+/// Returns the data of a layer relative to the origin of the scene, unless it already is.
+fn moved_to(ply_data: &PlyData, scene_origin: [f64; 3]) -> Option<PlyData> {
+    if ply_data.origin == scene_origin {
+        None
+    } else {
+        Some(PlyData::merged_at(&[ply_data], scene_origin))
+    }
+}
+
+/// Returns the views of a layer in the coordinates of the scene.
+fn views_in_scene(ply_data: &PlyData, scene_origin: [f64; 3]) -> Vec<CameraView> {
+    let offset = Vector3::from(ply_data.origin) - Vector3::from(scene_origin);
+    ply_data
+        .views
+        .iter()
+        .map(|view| view.moved(offset))
+        .collect()
+}
+
+/// Points the camera at newly imported data, which is shown relative to `scene_origin`.
+fn point_camera_at(
+    ply_data: &PlyData,
+    scene_origin: [f64; 3],
+    camera: &mut Camera,
+    app: &mut Interface,
+) {
+    // meshes may not have any vertices of their own outside of faces
+    let vertices = || {
+        ply_data
+            .point_vertices
+            .iter()
+            .chain(&ply_data.face_vertices)
+    };
+    if vertices().next().is_none() {
+        return;
+    }
+    let avg_pos = import::avg_vertex_position(vertices());
+    let avg_dist = import::avg_vertex_distance(avg_pos, vertices());
+    let offset = Vector3::from(ply_data.origin) - Vector3::from(scene_origin);
+
+    camera.set_camera_facing(avg_pos + offset.cast::<f32>(), avg_dist * 5.0);
+    app.set_camera_scale(avg_dist);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(name: &str) -> Message {
+        Message::Layer {
+            name: name.into(),
+            data: PlyData::default(),
+        }
+    }
+
+    fn views(layer: &str) -> Message {
+        Message::Views {
+            layer: layer.into(),
+            views: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn messages_replace_pending_data_of_their_layer() {
+        assert!(layer("a").replaces(&layer("a")));
+        assert!(layer("a").replaces(&views("a")));
+        assert!(views("a").replaces(&views("a")));
+        assert!(Message::RemoveLayer("a".into()).replaces(&layer("a")));
+        // views alone don't update the points of a layer
+        assert!(!views("a").replaces(&layer("a")));
+        assert!(!layer("a").replaces(&layer("b")));
+
+        let image = |name: &str| Message::Image {
+            name: name.into(),
+            image: DynamicImage::new_rgb8(1, 1),
+        };
+        assert!(image("camera").replaces(&image("camera")));
+        assert!(!image("camera").replaces(&image("depth")));
+        assert!(!image("a").replaces(&layer("a")));
+    }
+}
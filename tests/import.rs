@@ -1,2 +1,212 @@
+use ennona::{
+    export::{self, Encoding, ExportOptions},
+    import::{self, progress::Progress, Import, ImportErrorKind, ImportOptions, PlyData},
+};
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+const ASCII_PLY: &str = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+property float intensity
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0 0.5
+1 0 0 0 255 0 1.5
+0 1 0 0 0 255 2.5
+3 0 1 2
+";
+
+/// Writes `contents` to a file of the test in the temporary directory.
+fn write_file(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ennona-tests-{}", std::process::id()));
+    let path = dir.join(name);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn import_ply_data(path: &std::path::Path) -> PlyData {
+    match import::import(path, &ImportOptions::default(), &Progress::default()).unwrap() {
+        Import::Ply(ply_data) => ply_data,
+        _ => panic!("'{}' was not imported as geometry", path.display()),
+    }
+}
+
 #[test]
-fn import_ply() {}
+fn import_ply() {
+    let path = write_file("triangle.ply", ASCII_PLY.as_bytes());
+    let ply_data = import_ply_data(&path);
+
+    let positions = ply_data
+        .face_vertices
+        .iter()
+        .map(|vertex| vertex.position)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        positions,
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+    );
+    assert_eq!(ply_data.face_vertices[1].color, [0.0, 1.0, 0.0]);
+    assert_eq!(ply_data.face_indices, [0, 1, 2]);
+    assert_eq!(ply_data.scalar_fields[0].name, "intensity");
+    assert_eq!(ply_data.scalar_fields[0].face_values, [0.5, 1.5, 2.5]);
+}
+
+#[test]
+fn detects_format_without_extension() {
+    let pcd = "# .PCD v0.7
+VERSION 0.7
+FIELDS x y z
+SIZE 4 4 4
+TYPE F F F
+COUNT 1 1 1
+WIDTH 2
+HEIGHT 1
+POINTS 2
+DATA ascii
+1 2 3
+4 5 6
+";
+    let path = write_file("cloud", pcd.as_bytes());
+    let ply_data = import_ply_data(&path);
+    assert_eq!(ply_data.point_vertices.len(), 2);
+    assert_eq!(ply_data.point_vertices[1].position, [4.0, 5.0, 6.0]);
+}
+
+#[test]
+fn signature_takes_precedence_over_extension() {
+    let path = write_file("triangle.txt", ASCII_PLY.as_bytes());
+    assert_eq!(import_ply_data(&path).face_indices, [0, 1, 2]);
+}
+
+#[test]
+fn import_compressed() {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(ASCII_PLY.as_bytes()).unwrap();
+    let path = write_file("triangle.ply.gz", &encoder.finish().unwrap());
+    assert_eq!(import_ply_data(&path).face_vertices.len(), 3);
+}
+
+#[test]
+fn unknown_format() {
+    let path = write_file("noise", &[0xff, 0x00, 0xfe, 0x01]);
+    let error = import::import(&path, &ImportOptions::default(), &Progress::default())
+        .err()
+        .unwrap();
+    assert_eq!(error.path, path);
+    assert!(matches!(error.kind, ImportErrorKind::UnknownFormat));
+}
+
+#[test]
+fn cancelled_import() {
+    let path = write_file("cancelled.ply", ASCII_PLY.as_bytes());
+    let progress = Progress::new(|_| {}, Arc::new(AtomicBool::new(true)));
+    let error = import::import(&path, &ImportOptions::default(), &progress)
+        .err()
+        .unwrap();
+    assert!(matches!(error.kind, ImportErrorKind::Cancelled));
+}
+
+#[test]
+fn export_roundtrip() {
+    let original = import_ply_data(&write_file("original.ply", ASCII_PLY.as_bytes()));
+    for (name, encoding) in [
+        ("ascii.ply", Encoding::Ascii),
+        ("binary.ply", Encoding::Binary),
+    ] {
+        let path = write_file(name, b"");
+        export::export(&path, &original, &ExportOptions { encoding }).unwrap();
+        let exported = import_ply_data(&path);
+        assert_eq!(exported.face_indices, original.face_indices);
+        for (a, b) in exported.face_vertices.iter().zip(&original.face_vertices) {
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.color, b.color);
+        }
+        assert_eq!(
+            exported.scalar_fields[0].face_values,
+            original.scalar_fields[0].face_values
+        );
+    }
+}
+
+#[test]
+fn ply_next_to_colmap_model() {
+    let model = [
+        ("cameras.txt", "1 PINHOLE 640 480 500 500 320 240\n"),
+        ("images.txt", "1 1 0 0 0 0 0 0 1 image.jpg\n\n"),
+        ("points3D.txt", "1 0 0 1 255 255 255 0.5 1 0\n"),
+    ];
+    for (name, contents) in model {
+        write_file(&format!("model/{}", name), contents.as_bytes());
+    }
+    let dense = write_file("model/dense.ply", ASCII_PLY.as_bytes());
+    assert_eq!(import_ply_data(&dense).face_indices, [0, 1, 2]);
+
+    let sparse = import_ply_data(&dense.with_file_name("points3D.txt"));
+    assert_eq!(sparse.point_vertices.len(), 1);
+    assert_eq!(sparse.views.len(), 1);
+}
+
+#[test]
+fn directory_imports_report_progress() {
+    let model = [
+        ("cameras.txt", "1 PINHOLE 640 480 500 500 320 240\n"),
+        ("images.txt", "1 1 0 0 0 0 0 0 1 image.jpg\n\n"),
+        ("points3D.txt", "1 0 0 1 255 255 255 0.5 1 0\n"),
+    ];
+    let paths = model
+        .map(|(name, contents)| write_file(&format!("progress/{}", name), contents.as_bytes()));
+    let fractions = Arc::new(Mutex::new(Vec::new()));
+    let reported = fractions.clone();
+    let progress = Progress::new(
+        move |fraction| reported.lock().unwrap().push(fraction),
+        Arc::default(),
+    );
+    let dir = paths[0].parent().unwrap();
+    import::import(dir, &ImportOptions::default(), &progress).unwrap();
+    let fractions = fractions.lock().unwrap();
+    // the files of the model are reported together
+    assert!(fractions.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!(fractions.last(), Some(&1.0));
+
+    let scans = write_file("scans/000000.bin", &[0; 16])
+        .parent()
+        .unwrap()
+        .to_owned();
+    let progress = Progress::new(|_| {}, Arc::new(AtomicBool::new(true)));
+    let error = import::import(&scans, &ImportOptions::default(), &progress)
+        .err()
+        .unwrap();
+    assert!(matches!(error.kind, ImportErrorKind::Cancelled));
+}
+
+#[test]
+fn merged_layers_share_the_scene_origin() {
+    let local = import_ply_data(&write_file("local.ply", ASCII_PLY.as_bytes()));
+    let mut georeferenced = import_ply_data(&write_file("georeferenced.ply", ASCII_PLY.as_bytes()));
+    georeferenced.origin = [1000.0, 0.0, 0.0];
+    // the first layer with an origin sets the one of the scene
+    assert_eq!(
+        PlyData::scene_origin([&local, &georeferenced]),
+        [1000.0, 0.0, 0.0]
+    );
+
+    let merged = PlyData::merged(&[&local, &georeferenced]);
+    assert_eq!(merged.origin, [1000.0, 0.0, 0.0]);
+    assert_eq!(merged.face_vertices[1].position, [-999.0, 0.0, 0.0]);
+    assert_eq!(merged.face_vertices[4].position, [1.0, 0.0, 0.0]);
+    assert_eq!(merged.face_indices, [0, 1, 2, 3, 4, 5]);
+}